@@ -0,0 +1,55 @@
+//! Shared code for the examples.
+
+use std::io::{self, Write};
+
+use trezor::{ButtonRequestType, InteractionHandler, PinMatrixRequestType, PinRetry, Result};
+
+/// An interaction handler that asks the user on the console.
+///
+/// # Panics
+///
+/// Panics if reading from stdin fails.
+#[derive(Debug, Default)]
+pub struct ConsoleHandler;
+
+impl ConsoleHandler {
+	/// Print the prompt and read a line from stdin, without the trailing newline.
+	fn read_line(&self, prompt: &str) -> String {
+		print!("{}: ", prompt);
+		io::stdout().flush().expect("failed writing to stdout");
+		let mut line = String::new();
+		io::stdin().read_line(&mut line).expect("failed reading from stdin");
+		line.trim_end_matches(|c| c == '\r' || c == '\n').to_owned()
+	}
+}
+
+impl InteractionHandler for ConsoleHandler {
+	fn button(&mut self, request_type: ButtonRequestType) -> Result<()> {
+		println!("Confirm on the device ({:?})", request_type);
+		Ok(())
+	}
+
+	fn pin(&mut self, request_type: PinMatrixRequestType) -> Result<String> {
+		println!("The device requests a PIN ({:?}).", request_type);
+		Ok(self.read_line("Enter the PIN using the matrix on the device"))
+	}
+
+	fn passphrase(&mut self, on_device: bool) -> Result<Option<String>> {
+		if on_device {
+			println!("Enter the passphrase on the device");
+			Ok(None)
+		} else {
+			Ok(Some(self.read_line("Enter the passphrase")))
+		}
+	}
+
+	fn pin_invalid(&mut self, retry: &PinRetry) {
+		println!("Invalid PIN, {} retries left", retry.retries_left);
+		if retry.backoff.as_secs() > 0 {
+			println!(
+				"The device checks the next PIN in {} seconds or more",
+				retry.backoff.as_secs()
+			);
+		}
+	}
+}
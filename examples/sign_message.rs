@@ -4,11 +4,13 @@ extern crate hex;
 extern crate log;
 extern crate trezor;
 
-use std::io;
+mod common;
 
 use bitcoin::{network::constants::Network, util::bip32, Address};
 
-use trezor::{InputScriptType, TrezorMessage, TrezorResponse};
+use common::ConsoleHandler;
+use trezor::examples_support;
+use trezor::InputScriptType;
 
 fn setup_logger() {
 	fern::Dispatch::new()
@@ -21,68 +23,35 @@ fn setup_logger() {
 		.unwrap();
 }
 
-fn handle_interaction<T, R: TrezorMessage>(resp: TrezorResponse<T, R>) -> T {
-	match resp {
-		TrezorResponse::Ok(res) => res,
-		TrezorResponse::Failure(_) => resp.ok().unwrap(), // assering ok() returns the failure error
-		TrezorResponse::ButtonRequest(req) => handle_interaction(req.ack().unwrap()),
-		TrezorResponse::PinMatrixRequest(req) => {
-			println!("Enter PIN");
-			let mut pin = String::new();
-			if io::stdin().read_line(&mut pin).unwrap() != 5 {
-				println!("must enter pin, received: {}", pin);
-			}
-			// trim newline
-			handle_interaction(req.ack_pin(pin[..4].to_owned()).unwrap())
-		}
-		TrezorResponse::PassphraseRequest(req) => {
-			println!("Enter passphrase");
-			let mut pass = String::new();
-			io::stdin().read_line(&mut pass).unwrap();
-			// trim newline
-			handle_interaction(req.ack_passphrase(pass[..pass.len() - 1].to_owned()).unwrap())
-		}
-		TrezorResponse::PassphraseStateRequest(req) => handle_interaction(req.ack().unwrap()),
-	}
-}
-
 fn main() {
 	setup_logger();
 	// init with debugging
-	let mut trezor = trezor::unique(true).unwrap();
-	trezor.init_device().unwrap();
-
-	let pubkey = handle_interaction(
-		trezor
-			.get_public_key(
-				vec![
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(1).unwrap(),
-				],
-				trezor::protos::InputScriptType::SPENDADDRESS,
-				Network::Testnet,
-				true,
-			)
-			.unwrap(),
-	);
+	let mut trezor = examples_support::connect(true).unwrap();
+
+	let path: bip32::DerivationPath = vec![
+		bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+		bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+		bip32::ChildNumber::from_hardened_idx(1).unwrap(),
+	]
+	.into();
+	let pubkey = trezor
+		.get_public_key(&path, InputScriptType::SpendAddress, Network::Testnet, true)
+		.unwrap()
+		.interact(&mut ConsoleHandler)
+		.unwrap();
 	let addr = Address::p2pkh(&pubkey.public_key, Network::Testnet);
 	println!("address: {}", addr);
 
-	let (addr, signature) = handle_interaction(
-		trezor
-			.sign_message(
-				"regel het".to_owned(),
-				vec![
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(1).unwrap(),
-				],
-				InputScriptType::SPENDADDRESS,
-				Network::Testnet,
-			)
-			.unwrap(),
-	);
+	let (addr, signature) = trezor
+		.sign_message(
+			"regel het".to_owned(),
+			&path,
+			InputScriptType::SpendAddress,
+			Network::Testnet,
+		)
+		.unwrap()
+		.interact(&mut ConsoleHandler)
+		.unwrap();
 	println!("Addr from device: {}", addr);
 	println!("Signature: {:?}", signature);
 }
@@ -4,15 +4,19 @@ extern crate hex;
 extern crate log;
 extern crate trezor;
 
+mod common;
+
 use std::collections::HashMap;
-use std::io::{self, Write};
+use std::io::Write;
 
 use bitcoin::{
 	blockdata::script::Builder, consensus::encode::Decodable, network::constants::Network,
 	util::bip32, util::hash::BitcoinHash, util::psbt, Address, OutPoint, Transaction, TxIn, TxOut,
 };
 
-use trezor::{Error, SignTxProgress, TrezorMessage, TrezorResponse};
+use common::ConsoleHandler;
+use trezor::examples_support;
+use trezor::{Error, SignTxProgress};
 
 fn setup_logger() {
 	fern::Dispatch::new()
@@ -25,31 +29,6 @@ fn setup_logger() {
 		.unwrap();
 }
 
-fn handle_interaction<T, R: TrezorMessage>(resp: TrezorResponse<T, R>) -> T {
-	match resp {
-		TrezorResponse::Ok(res) => res,
-		TrezorResponse::Failure(_) => resp.ok().unwrap(), // assering ok() returns the failure error
-		TrezorResponse::ButtonRequest(req) => handle_interaction(req.ack().unwrap()),
-		TrezorResponse::PinMatrixRequest(req) => {
-			println!("Enter PIN");
-			let mut pin = String::new();
-			if io::stdin().read_line(&mut pin).unwrap() != 5 {
-				println!("must enter pin, received: {}", pin);
-			}
-			// trim newline
-			handle_interaction(req.ack_pin(pin[..4].to_owned()).unwrap())
-		}
-		TrezorResponse::PassphraseRequest(req) => {
-			println!("Enter passphrase");
-			let mut pass = String::new();
-			io::stdin().read_line(&mut pass).unwrap();
-			// trim newline
-			handle_interaction(req.ack_passphrase(pass[..pass.len() - 1].to_owned()).unwrap())
-		}
-		TrezorResponse::PassphraseStateRequest(req) => handle_interaction(req.ack().unwrap()),
-	}
-}
-
 fn tx_progress(
 	psbt: &mut psbt::PartiallySignedTransaction,
 	progress: SignTxProgress,
@@ -60,7 +39,7 @@ fn tx_progress(
 	}
 
 	if !progress.finished() {
-		let progress = handle_interaction(progress.ack_psbt(&psbt, Network::Testnet).unwrap());
+		let progress = progress.ack_psbt(&psbt, Network::Testnet)?.interact(&mut ConsoleHandler)?;
 		tx_progress(psbt, progress, raw_tx)
 	} else {
 		Ok(())
@@ -70,23 +49,19 @@ fn tx_progress(
 fn main() {
 	setup_logger();
 	// init with debugging
-	let mut trezor = trezor::unique(true).unwrap();
-	trezor.init_device().unwrap();
+	let mut trezor = examples_support::connect(true).unwrap();
 
-	let pubkey = handle_interaction(
-		trezor
-			.get_public_key(
-				vec![
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(0).unwrap(),
-					bip32::ChildNumber::from_hardened_idx(1).unwrap(),
-				],
-				trezor::protos::InputScriptType::SPENDADDRESS,
-				Network::Testnet,
-				true,
-			)
-			.unwrap(),
-	);
+	let path: bip32::DerivationPath = vec![
+		bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+		bip32::ChildNumber::from_hardened_idx(0).unwrap(),
+		bip32::ChildNumber::from_hardened_idx(1).unwrap(),
+	]
+	.into();
+	let pubkey = trezor
+		.get_public_key(&path, trezor::InputScriptType::SpendAddress, Network::Testnet, true)
+		.unwrap()
+		.interact(&mut ConsoleHandler)
+		.unwrap();
 	let addr = Address::p2pkh(&pubkey.public_key, Network::Testnet);
 	println!("address: {}", addr);
 
@@ -127,7 +102,8 @@ fn main() {
 	);
 
 	let mut raw_tx = Vec::new();
-	let progress = handle_interaction(trezor.sign_tx(&psbt, Network::Testnet).unwrap());
+	let progress =
+		trezor.sign_tx(&psbt, Network::Testnet).unwrap().interact(&mut ConsoleHandler).unwrap();
 	tx_progress(&mut psbt, progress, &mut raw_tx).unwrap();
 
 	println!("signed tx: {}", hex::encode(raw_tx));
@@ -1,3 +1,3 @@
 #!/bin/sh
 
-protoc --proto_path ./protos --rust_out ./src/protos protos/*.proto
+protoc --proto_path ./protos --rust_out ./trezor-protos/src protos/*.proto
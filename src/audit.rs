@@ -0,0 +1,203 @@
+//! # Audit log
+//!
+//! An optional audit log that records every security-relevant operation performed through the
+//! client.  Events are passed to a caller-provided `AuditSink`, for example to write them to a
+//! log file for compliance purposes.
+//!
+//! Operations that change the device, like wiping it or changing the settings, are recorded once
+//! the device reports their success, so cancelled or failed operations are not recorded.  Key
+//! and signing requests are recorded when they are sent to the device.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use hex;
+
+use types::{InputScriptType, PassphraseMode};
+use utils;
+
+/// A transaction output as presented to the device for signing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditOutput {
+	/// The destination address, or the hex-encoded script if it has no address.
+	pub destination: String,
+	/// The amount in satoshis.
+	pub amount: u64,
+	/// The derivation path if the output is change.
+	pub change_path: Option<Vec<u32>>,
+}
+
+/// A security-relevant operation performed on the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditEvent {
+	/// A public key was requested.
+	PublicKeyRequested {
+		path: Vec<u32>,
+		script_type: InputScriptType,
+		coin_name: String,
+		show_display: bool,
+	},
+	/// An address was retrieved from the device.
+	AddressRetrieved {
+		path: Vec<u32>,
+		script_type: InputScriptType,
+		coin_name: String,
+		show_display: bool,
+		address: String,
+	},
+	/// A transaction was sent to the device for signing.
+	TransactionSigningStarted {
+		coin_name: String,
+		inputs: Vec<String>,
+		outputs: Vec<AuditOutput>,
+	},
+	/// A message was sent to the device for signing.
+	MessageSigningStarted {
+		path: Vec<u32>,
+		coin_name: String,
+		message: Vec<u8>,
+	},
+	/// The device settings were changed.
+	SettingsChanged {
+		label: Option<String>,
+		use_passphrase: Option<bool>,
+		homescreen_changed: bool,
+		passphrase_mode: Option<PassphraseMode>,
+		auto_lock_delay_ms: Option<usize>,
+	},
+	/// The PIN was changed or removed.
+	PinChanged {
+		remove: bool,
+	},
+	/// The device was wiped.
+	DeviceWiped,
+	/// A new seed was generated on the device.
+	DeviceReset {
+		strength: usize,
+		passphrase_protection: bool,
+		pin_protection: bool,
+	},
+	/// A seed was loaded onto the device from a mnemonic.
+	DeviceLoaded {
+		passphrase_protection: bool,
+		pin_protection: bool,
+	},
+	/// The device was recovered from a seed, or a seed was checked in a dry run.
+	DeviceRecovered {
+		word_count: usize,
+		dry_run: bool,
+	},
+	/// A backup of the seed was started.
+	BackupStarted,
+}
+
+impl fmt::Display for AuditEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			AuditEvent::PublicKeyRequested {
+				ref path,
+				ref script_type,
+				ref coin_name,
+				show_display,
+			} => write!(
+				f,
+				"event=public_key path={} script_type={:?} coin={} show_display={}",
+				utils::path_to_string(path),
+				script_type,
+				coin_name,
+				show_display
+			),
+			AuditEvent::AddressRetrieved {
+				ref path,
+				ref script_type,
+				ref coin_name,
+				show_display,
+				ref address,
+			} => write!(
+				f,
+				"event=address path={} script_type={:?} coin={} show_display={} address={}",
+				utils::path_to_string(path),
+				script_type,
+				coin_name,
+				show_display,
+				address
+			),
+			AuditEvent::TransactionSigningStarted {
+				ref coin_name,
+				ref inputs,
+				ref outputs,
+			} => {
+				write!(f, "event=sign_tx coin={} inputs={}", coin_name, inputs.join(","))?;
+				for output in outputs {
+					write!(f, " output={}:{}", output.destination, output.amount)?;
+					if let Some(ref path) = output.change_path {
+						write!(f, ":change={}", utils::path_to_string(path))?;
+					}
+				}
+				Ok(())
+			}
+			AuditEvent::MessageSigningStarted {
+				ref path,
+				ref coin_name,
+				ref message,
+			} => write!(
+				f,
+				"event=sign_message path={} coin={} message={}",
+				utils::path_to_string(path),
+				coin_name,
+				hex::encode(message)
+			),
+			AuditEvent::SettingsChanged {
+				ref label,
+				use_passphrase,
+				homescreen_changed,
+				passphrase_mode,
+				auto_lock_delay_ms,
+			} => write!(
+				f,
+				"event=apply_settings label={:?} use_passphrase={:?} homescreen_changed={} \
+				 passphrase_mode={:?} auto_lock_delay_ms={:?}",
+				label, use_passphrase, homescreen_changed, passphrase_mode, auto_lock_delay_ms
+			),
+			AuditEvent::PinChanged {
+				remove,
+			} => write!(f, "event=change_pin remove={}", remove),
+			AuditEvent::DeviceWiped => f.write_str("event=wipe_device"),
+			AuditEvent::DeviceReset {
+				strength,
+				passphrase_protection,
+				pin_protection,
+			} => write!(
+				f,
+				"event=reset_device strength={} passphrase_protection={} pin_protection={}",
+				strength, passphrase_protection, pin_protection
+			),
+			AuditEvent::DeviceLoaded {
+				passphrase_protection,
+				pin_protection,
+			} => write!(
+				f,
+				"event=load_device passphrase_protection={} pin_protection={}",
+				passphrase_protection, pin_protection
+			),
+			AuditEvent::DeviceRecovered {
+				word_count,
+				dry_run,
+			} => write!(f, "event=recover_device word_count={} dry_run={}", word_count, dry_run),
+			AuditEvent::BackupStarted => f.write_str("event=backup_device"),
+		}
+	}
+}
+
+/// An audit event with the time it was recorded.
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+	pub time: SystemTime,
+	pub event: AuditEvent,
+}
+
+/// A sink for audit records.  The sink is provided by the user of the library and is
+/// responsible for storing the records.
+pub trait AuditSink {
+	fn record(&mut self, record: AuditRecord);
+}
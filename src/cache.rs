@@ -0,0 +1,66 @@
+//!
+//! In-memory cache for public keys and addresses retrieved from the device.
+//!
+
+use std::collections::HashMap;
+
+use bitcoin::util::bip32;
+use bitcoin::Address;
+
+use types::InputScriptType;
+
+/// The key under which public keys and addresses are cached.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+	path: Vec<u32>,
+	script_type: InputScriptType,
+	coin_name: String,
+	/// The passphrase state of the session, if known.
+	session: Option<Vec<u8>>,
+}
+
+impl CacheKey {
+	pub fn new(
+		path: Vec<u32>,
+		script_type: InputScriptType,
+		coin_name: String,
+		session: Option<Vec<u8>>,
+	) -> CacheKey {
+		CacheKey {
+			path: path,
+			script_type: script_type,
+			coin_name: coin_name,
+			session: session,
+		}
+	}
+}
+
+/// A cache for extended public keys and addresses.
+#[derive(Debug, Default)]
+pub struct KeyCache {
+	xpubs: HashMap<CacheKey, bip32::ExtendedPubKey>,
+	addresses: HashMap<CacheKey, Address>,
+}
+
+impl KeyCache {
+	pub fn xpub(&self, key: &CacheKey) -> Option<bip32::ExtendedPubKey> {
+		self.xpubs.get(key).cloned()
+	}
+
+	pub fn insert_xpub(&mut self, key: CacheKey, xpub: bip32::ExtendedPubKey) {
+		self.xpubs.insert(key, xpub);
+	}
+
+	pub fn address(&self, key: &CacheKey) -> Option<Address> {
+		self.addresses.get(key).cloned()
+	}
+
+	pub fn insert_address(&mut self, key: CacheKey, address: Address) {
+		self.addresses.insert(key, address);
+	}
+
+	pub fn clear(&mut self) {
+		self.xpubs.clear();
+		self.addresses.clear();
+	}
+}
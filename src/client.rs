@@ -1,28 +1,67 @@
+use std::cell::RefCell;
+use std::cmp;
 use std::fmt;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
 
 use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
 use bitcoin::util::bip32;
 use bitcoin::util::psbt;
 use bitcoin::Address;
 use hex;
+use protobuf::Message;
 use secp256k1;
 use unicode_normalization::UnicodeNormalization;
 
-use super::Model;
+use super::{HidVersion, Model};
+use audit::{AuditEvent, AuditOutput, AuditRecord, AuditSink};
+use cache::{CacheKey, KeyCache};
 use error::{Error, Result};
+use export::{self, ElectrumAccount};
+use flows::entropy_check::EntropyCheck;
 use flows::sign_tx::SignTxProgress;
+use flows::stellar_sign_tx::StellarSignTxProgress;
+use flows::wipe_device::{WipeDeviceConfirmation, WipeDeviceResponse};
+use interaction::{
+	self, InteractionAnswer, InteractionHandler, InteractionToken, PinRetry, ProgressEvent,
+};
+use logging::PayloadLogConfig;
 use messages::TrezorMessage;
+use policy::{PolicyDecision, SigningPolicy, SigningRequest};
 use protos;
 use protos::MessageType::*;
-use transport::{ProtoMessage, Transport};
+use stellar;
+use transport::{self, ProtoMessage, Transport};
+use types::{
+	AddressOptions, AddressType, BootloaderInfo, ButtonRequestType, Capability, Chain,
+	DeviceFeatures, Failure, FailureCode, InputScriptType, MnemonicOptions, PassphraseMode,
+	PinMatrixRequestType,
+};
 use utils;
 
-// Some types with raw protos that we use in the public interface so they have to be exported.
-use protos::ApplySettings_PassphraseSourceType as PassphraseSource;
-pub use protos::ButtonRequest_ButtonRequestType as ButtonRequestType;
-pub use protos::Features;
-pub use protos::InputScriptType;
-pub use protos::PinMatrixRequest_PinMatrixRequestType as PinMatrixRequestType;
+/// The field number of `passphrase_always_on_device` in the ApplySettings message of newer
+/// firmware.  It replaced `passphrase_source` in firmware 1.9.0 and 2.3.0.
+const APPLY_SETTINGS_PASSPHRASE_ALWAYS_ON_DEVICE: u32 = 8;
+
+/// The field numbers of `encoded_network` and `chunkify` in the EthereumGetAddress message of
+/// newer firmware.
+const ETHEREUM_GET_ADDRESS_ENCODED_NETWORK: u32 = 3;
+const ETHEREUM_GET_ADDRESS_CHUNKIFY: u32 = 4;
+
+/// The field number of the checksummed string `address` in the EthereumAddress message of newer
+/// firmware.  It replaced the raw address bytes.
+const ETHEREUM_ADDRESS_STRING: u32 = 2;
+
+/// The field number of the string `address` in the EthereumVerifyMessage message of newer
+/// firmware.  It replaced the raw address bytes.
+const ETHEREUM_VERIFY_MESSAGE_ADDRESS_STRING: u32 = 4;
+
+/// The interval at which `ProgressEvent::WaitingForUser` events are sent.
+const WAITING_FOR_USER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The time to wait for the device to answer a Cancel.
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
 
 /// The different options for the number of words in a seed phrase.
 pub enum WordCount {
@@ -61,7 +100,7 @@ impl<'a, T, R: TrezorMessage> fmt::Debug for ButtonRequest<'a, T, R> {
 impl<'a, T, R: TrezorMessage> ButtonRequest<'a, T, R> {
 	/// The type of button request.
 	pub fn request_type(&self) -> ButtonRequestType {
-		self.message.get_code()
+		self.message.get_code().into()
 	}
 
 	/// The metadata sent with the button request.
@@ -92,7 +131,7 @@ impl<'a, T, R: TrezorMessage> fmt::Debug for PinMatrixRequest<'a, T, R> {
 impl<'a, T, R: TrezorMessage> PinMatrixRequest<'a, T, R> {
 	/// The type of PIN matrix request.
 	pub fn request_type(&self) -> PinMatrixRequestType {
-		self.message.get_field_type()
+		self.message.get_field_type().into()
 	}
 
 	/// Ack the request with a PIN and get the next message from the device.
@@ -168,7 +207,7 @@ impl<'a, T, R: TrezorMessage> PassphraseStateRequest<'a, T, R> {
 #[derive(Debug)]
 pub enum TrezorResponse<'a, T, R: TrezorMessage> {
 	Ok(T),
-	Failure(protos::Failure),
+	Failure(Failure),
 	ButtonRequest(ButtonRequest<'a, T, R>),
 	PinMatrixRequest(PinMatrixRequest<'a, T, R>),
 	PassphraseRequest(PassphraseRequest<'a, T, R>),
@@ -214,11 +253,35 @@ impl<'a, T, R: TrezorMessage> TrezorResponse<'a, T, R> {
 		}
 	}
 
+	/// Resolve all interaction requests using the given handler and get the `Ok` response value.
+	pub fn interact<H: InteractionHandler + ?Sized>(self, handler: &mut H) -> Result<T> {
+		let mut resp = self;
+		loop {
+			resp = match resp {
+				TrezorResponse::Ok(m) => return Ok(m),
+				TrezorResponse::Failure(m) => return Err(Error::FailureResponse(m)),
+				TrezorResponse::ButtonRequest(r) => {
+					handler.button(r.request_type())?;
+					r.ack()?
+				}
+				TrezorResponse::PinMatrixRequest(r) => {
+					let pin = handler.pin(r.request_type())?;
+					r.ack_pin(pin)?
+				}
+				TrezorResponse::PassphraseRequest(r) => match handler.passphrase(r.on_device())? {
+					Some(passphrase) => r.ack_passphrase(passphrase)?,
+					None => r.ack()?,
+				},
+				TrezorResponse::PassphraseStateRequest(r) => r.ack()?,
+			};
+		}
+	}
+
 	/// Get the button request object or an error if not `ButtonRequest`.
 	pub fn button_request(self) -> Result<ButtonRequest<'a, T, R>> {
 		match self {
 			TrezorResponse::ButtonRequest(r) => Ok(r),
-			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type())),
+			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type().into())),
 			TrezorResponse::Failure(m) => Err(Error::FailureResponse(m)),
 			TrezorResponse::PinMatrixRequest(_) => {
 				Err(Error::UnexpectedInteractionRequest(InteractionType::PinMatrix))
@@ -236,7 +299,7 @@ impl<'a, T, R: TrezorMessage> TrezorResponse<'a, T, R> {
 	pub fn pin_matrix_request(self) -> Result<PinMatrixRequest<'a, T, R>> {
 		match self {
 			TrezorResponse::PinMatrixRequest(r) => Ok(r),
-			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type())),
+			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type().into())),
 			TrezorResponse::Failure(m) => Err(Error::FailureResponse(m)),
 			TrezorResponse::ButtonRequest(_) => {
 				Err(Error::UnexpectedInteractionRequest(InteractionType::Button))
@@ -254,7 +317,7 @@ impl<'a, T, R: TrezorMessage> TrezorResponse<'a, T, R> {
 	pub fn passphrase_request(self) -> Result<PassphraseRequest<'a, T, R>> {
 		match self {
 			TrezorResponse::PassphraseRequest(r) => Ok(r),
-			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type())),
+			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type().into())),
 			TrezorResponse::Failure(m) => Err(Error::FailureResponse(m)),
 			TrezorResponse::ButtonRequest(_) => {
 				Err(Error::UnexpectedInteractionRequest(InteractionType::Button))
@@ -272,7 +335,7 @@ impl<'a, T, R: TrezorMessage> TrezorResponse<'a, T, R> {
 	pub fn passphrase_state_request(self) -> Result<PassphraseStateRequest<'a, T, R>> {
 		match self {
 			TrezorResponse::PassphraseStateRequest(r) => Ok(r),
-			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type())),
+			TrezorResponse::Ok(_) => Err(Error::UnexpectedMessageType(R::message_type().into())),
 			TrezorResponse::Failure(m) => Err(Error::FailureResponse(m)),
 			TrezorResponse::ButtonRequest(_) => {
 				Err(Error::UnexpectedInteractionRequest(InteractionType::Button))
@@ -290,6 +353,9 @@ impl<'a, T, R: TrezorMessage> TrezorResponse<'a, T, R> {
 /// When resetting the device, it will ask for entropy to aid key generation.
 pub struct EntropyRequest<'a> {
 	client: &'a mut Trezor,
+	strength: usize,
+	/// The audit event recorded once the device is reset.
+	event: AuditEvent,
 }
 
 impl<'a> EntropyRequest<'a> {
@@ -301,7 +367,97 @@ impl<'a> EntropyRequest<'a> {
 
 		let mut req = protos::EntropyAck::new();
 		req.set_entropy(entropy);
-		self.client.call(req, Box::new(|_, _| Ok(())))
+		let event = self.event;
+		self.client.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(event.clone());
+				Ok(())
+			}),
+		)
+	}
+
+	/// Provide exactly 32 bytes of entropy and keep track of it so that the resulting seed can be
+	/// verified against the internal entropy shown on the device when `display_random` was set.
+	pub fn ack_entropy_checked(
+		self,
+		entropy: Vec<u8>,
+	) -> Result<TrezorResponse<'a, EntropyCheck, protos::Success>> {
+		if entropy.len() != 32 {
+			return Err(Error::InvalidEntropy);
+		}
+
+		let check = EntropyCheck::new(entropy.clone(), self.strength);
+		let mut req = protos::EntropyAck::new();
+		req.set_entropy(entropy);
+		let event = self.event;
+		self.client.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(event.clone());
+				Ok(check.clone())
+			}),
+		)
+	}
+}
+
+/// Whether the message type is an answer to an interaction request.
+fn is_interaction_ack(mtype: protos::MessageType) -> bool {
+	match mtype {
+		MessageType_ButtonAck
+		| MessageType_PinMatrixAck
+		| MessageType_PassphraseAck
+		| MessageType_PassphraseStateAck => true,
+		_ => false,
+	}
+}
+
+/// An interaction request that was returned as an `Error::InteractionRequired`.
+struct PendingInteraction {
+	token: InteractionToken,
+	kind: InteractionType,
+	/// The type of the message that started the operation.
+	request_type: Option<protos::MessageType>,
+	/// The signing request that was checked by the signing policy for the operation.
+	signing_request: Option<SigningRequest>,
+}
+
+/// The answer to a pending interaction, sent instead of the request of the resumed operation.
+struct ResumeAck {
+	/// The type of the message that started the operation.
+	request_type: protos::MessageType,
+	/// The interaction that is answered.
+	interaction: PendingInteraction,
+	ack: ProtoMessage,
+}
+
+/// Restores the pending interaction when a resumed operation returns without sending the
+/// answer, f.e. because it failed before anything was sent.  The device is still waiting for the
+/// answer then, so the operation can be resumed again.
+struct ResumeGuard {
+	pending_interaction: Rc<RefCell<Option<PendingInteraction>>>,
+	resume_ack: Rc<RefCell<Option<ResumeAck>>>,
+}
+
+impl Drop for ResumeGuard {
+	fn drop(&mut self) {
+		if let Some(resume) = self.resume_ack.borrow_mut().take() {
+			debug!("Resumed operation didn't send the answer, keeping the interaction pending");
+			*self.pending_interaction.borrow_mut() = Some(resume.interaction);
+		}
+	}
+}
+
+/// Restores the deadline of the enclosing flow when a flow run with `run_with_deadline()` returns
+/// or panics.
+struct DeadlineGuard<'a> {
+	client: &'a mut Trezor,
+	previous: Option<Instant>,
+}
+
+impl<'a> Drop for DeadlineGuard<'a> {
+	fn drop(&mut self) {
+		self.client.deadline = self.previous;
 	}
 }
 
@@ -309,7 +465,36 @@ impl<'a> EntropyRequest<'a> {
 pub struct Trezor {
 	model: Model,
 	// Cached features for later inspection.
-	features: Option<protos::Features>,
+	features: Option<DeviceFeatures>,
+	// The passphrase state of the current session, if known.
+	passphrase_state: Option<Vec<u8>>,
+	// Cache for public keys and addresses, if enabled.
+	cache: Option<KeyCache>,
+	// Sink for audit records, if enabled.
+	audit_sink: Option<Box<AuditSink>>,
+	// Policy consulted before signing, if set.
+	signing_policy: Option<Box<SigningPolicy>>,
+	// Whether interaction requests are returned as resumable errors.
+	resumable_interactions: bool,
+	// The type of the message that started the current operation.
+	request_type: Option<protos::MessageType>,
+	// The signing request that was checked by the signing policy for the current operation.
+	signing_request: Option<SigningRequest>,
+	// The interaction request the device is waiting for an answer to.  Shared with the guard in
+	// `resume()`.
+	pending_interaction: Rc<RefCell<Option<PendingInteraction>>>,
+	// The answer to send instead of the next request when resuming an operation.  Shared with
+	// the guard in `resume()`.
+	resume_ack: Rc<RefCell<Option<ResumeAck>>>,
+	next_interaction_token: u64,
+	// Channel for progress events, if set.
+	progress_sender: Option<mpsc::Sender<ProgressEvent>>,
+	// The maximum time to wait for the user to confirm on the device.
+	interaction_timeout: Option<Duration>,
+	// How message payloads are logged.
+	payload_log: PayloadLogConfig,
+	// The deadline of the flow run with `run_with_deadline()`.
+	deadline: Option<Instant>,
 	transport: Box<Transport>,
 }
 
@@ -319,6 +504,20 @@ pub fn trezor_with_transport(model: Model, transport: Box<Transport>) -> Trezor
 		model: model,
 		transport: transport,
 		features: None,
+		passphrase_state: None,
+		cache: None,
+		audit_sink: None,
+		signing_policy: None,
+		resumable_interactions: false,
+		request_type: None,
+		signing_request: None,
+		pending_interaction: Rc::new(RefCell::new(None)),
+		resume_ack: Rc::new(RefCell::new(None)),
+		next_interaction_token: 0,
+		progress_sender: None,
+		interaction_timeout: None,
+		payload_log: PayloadLogConfig::default(),
+		deadline: None,
 	}
 }
 
@@ -328,18 +527,379 @@ impl Trezor {
 		self.model
 	}
 
+	/// Get the HID version used to communicate with the device, if it is connected over HID.
+	pub fn hid_version(&self) -> Option<HidVersion> {
+		self.transport.hid_version()
+	}
+
 	/// Get the features of the Trezor device.
-	pub fn features(&self) -> Option<&protos::Features> {
+	pub fn features(&self) -> Option<&DeviceFeatures> {
 		self.features.as_ref()
 	}
 
+	/// Get the bootloader and installed firmware info.  Returns None if the device is not in
+	/// bootloader mode.
+	pub fn bootloader_info(&self) -> Result<Option<BootloaderInfo>> {
+		let features = self.features.as_ref().ok_or(Error::NotInitialized)?;
+		Ok(BootloaderInfo::from_features(features))
+	}
+
+	/// Check whether the device firmware uses the passphrase_always_on_device setting instead of
+	/// the older passphrase_source setting.  Returns None if the features are not known.
+	fn uses_passphrase_always_on_device(&self) -> Option<bool> {
+		let version = self.features.as_ref()?.version();
+		Some(match self.model {
+			Model::Trezor1 => version >= (1, 9, 0),
+			Model::Trezor2 | Model::Trezor2Bl => version >= (2, 3, 0),
+		})
+	}
+
+	/// Check whether the device supports the capability, so applications can hide unsupported
+	/// features.  The capabilities reported by the device are used if available, otherwise they
+	/// are derived from the model and firmware version.  Returns false if the features are not
+	/// known or the device is in bootloader mode.
+	///
+	/// For `Capability::Taproot`, true only means that the firmware can sign Taproot inputs.  The
+	/// messages of this crate have no Taproot input script type, so it can't sign them yet.
+	pub fn supports(&self, capability: Capability) -> bool {
+		let features = match self.features {
+			Some(ref f) if !f.bootloader_mode() => f,
+			_ => return false,
+		};
+		let version = features.version();
+		let trezor1 = self.model == Model::Trezor1;
+		match features.capabilities() {
+			Some(capabilities) if capability != Capability::Taproot => {
+				return capabilities.contains(&capability)
+			}
+			_ => {}
+		}
+
+		// Older firmware doesn't report its capabilities and no firmware reports Taproot.
+		match capability {
+			Capability::Bitcoin
+			| Capability::BitcoinLike
+			| Capability::Crypto
+			| Capability::Ethereum
+			| Capability::Lisk
+			| Capability::Nem
+			| Capability::Stellar
+			| Capability::U2f => true,
+			Capability::Binance
+			| Capability::Cardano
+			| Capability::Eos
+			| Capability::Monero
+			| Capability::Ripple
+			| Capability::Tezos => !trezor1,
+			Capability::Shamir => !trezor1 && version >= (2, 1, 0),
+			Capability::ShamirGroups => !trezor1 && version >= (2, 2, 0),
+			Capability::PassphraseEntry => !trezor1 && version >= (2, 3, 0),
+			Capability::Taproot if trezor1 => version >= (1, 10, 4),
+			Capability::Taproot => version >= (2, 4, 3),
+		}
+	}
+
+	/// Set the sink to which audit records are passed for every security-relevant operation.
+	pub fn set_audit_sink(&mut self, sink: Option<Box<AuditSink>>) {
+		self.audit_sink = sink;
+	}
+
+	/// Record an audit event if an audit sink is set.
+	fn audit(&mut self, event: AuditEvent) {
+		if let Some(ref mut sink) = self.audit_sink {
+			sink.record(AuditRecord {
+				time: SystemTime::now(),
+				event: event,
+			});
+		}
+	}
+
+	/// Set the policy that is consulted before every signing request.  Requests denied by the
+	/// policy fail with `Error::PolicyDenied` before anything is sent to the device.
+	pub fn set_signing_policy(&mut self, policy: Option<Box<SigningPolicy>>) {
+		self.signing_policy = policy;
+	}
+
+	/// Check the signing request against the signing policy if one is set.  A resumed operation
+	/// was checked when it was started, so it must be for the same request.
+	fn check_policy(&mut self, request: SigningRequest) -> Result<()> {
+		let resumed =
+			self.resume_ack.borrow().as_ref().map(|r| r.interaction.signing_request.clone());
+		if let Some(checked) = resumed {
+			if checked.as_ref() != Some(&request) {
+				return Err(Error::InvalidInteractionToken);
+			}
+			return Ok(());
+		}
+		if let Some(ref mut policy) = self.signing_policy {
+			if let PolicyDecision::Deny(reason) = policy.check(&request) {
+				return Err(Error::PolicyDenied(reason));
+			}
+		}
+		self.signing_request = Some(request);
+		Ok(())
+	}
+
+	/// Enable or disable caching of public keys and addresses.  When enabled, requests that don't
+	/// display anything on the device are answered from the cache if possible.  The cache is
+	/// keyed by the passphrase session and is cleared when the session changes, the device is
+	/// wiped or the settings are changed.  With passphrase protection enabled, nothing is cached
+	/// unless the device reports the passphrase state, which the Trezor One doesn't.
+	pub fn set_cache_enabled(&mut self, enabled: bool) {
+		self.cache = if enabled {
+			Some(KeyCache::default())
+		} else {
+			None
+		};
+	}
+
+	/// Clear the cache of public keys and addresses.
+	pub fn clear_cache(&mut self) {
+		if let Some(ref mut cache) = self.cache {
+			cache.clear();
+		}
+	}
+
+	/// Create the cache key for the given request parameters in the current session.  Returns
+	/// None if the wallet of the session is unknown: when passphrase protection is enabled, but
+	/// the device didn't report a passphrase state, like the Trezor One, every request can be for
+	/// a different passphrase.
+	fn cache_key(
+		&self,
+		path: Vec<u32>,
+		script_type: InputScriptType,
+		coin_name: String,
+	) -> Option<CacheKey> {
+		let passphrase_protection = self.features.as_ref()?.passphrase_protection();
+		if passphrase_protection && self.passphrase_state.is_none() {
+			return None;
+		}
+		Some(CacheKey::new(path, script_type, coin_name, self.passphrase_state.clone()))
+	}
+
+	/// Set the maximum size of messages accepted from the device.  Messages announcing a larger
+	/// size are rejected with `Error::MessageTooLarge` before any data is allocated for them.  The
+	/// rest of such a message is discarded, so the connection can still be used.  The default is
+	/// 4 MiB.
+	pub fn set_max_message_size(&mut self, size: usize) {
+		self.transport.set_max_message_size(size);
+	}
+
 	/// Sends a message and returns the raw ProtoMessage struct that was responded by the device.
 	/// This method is only exported for users that want to expand the features of this library
 	/// f.e. for supporting additional coins etc.
 	pub fn call_raw<S: TrezorMessage>(&mut self, message: S) -> Result<ProtoMessage> {
 		let proto_msg = ProtoMessage(S::message_type(), message.write_to_bytes()?);
+		self.call_proto(proto_msg)
+	}
+
+	/// Sends a ProtoMessage and returns the ProtoMessage responded by the device.
+	fn call_proto(&mut self, proto_msg: ProtoMessage) -> Result<ProtoMessage> {
+		if self.deadline.map(|d| Instant::now() >= d).unwrap_or(false) {
+			return Err(self.cancel_for_deadline());
+		}
+		// After a button ack, the response only comes when the user confirms on the device.
+		let waits_for_user = proto_msg.message_type() == MessageType_ButtonAck;
+		self.write_message(proto_msg)?;
+		if waits_for_user {
+			self.read_waiting_for_user()
+		} else {
+			self.read_message()
+		}
+	}
+
+	/// Write a message to the device, within the deadline if one is set.
+	fn write_message(&mut self, proto_msg: ProtoMessage) -> Result<()> {
+		let deadline = match self.deadline {
+			Some(deadline) => deadline,
+			None => {
+				return self.transport.write_message(proto_msg).map_err(Error::TransportSendMessage)
+			}
+		};
+		match self.transport.write_message_until(proto_msg, deadline) {
+			Ok(()) => Ok(()),
+			Err(transport::error::Error::DeviceWriteTimeout) => Err(self.cancel_for_deadline()),
+			Err(e) => Err(Error::TransportSendMessage(e)),
+		}
+	}
+
+	/// Read the next message from the device, within the deadline if one is set.
+	fn read_message(&mut self) -> Result<ProtoMessage> {
+		let deadline = match self.deadline {
+			Some(deadline) => deadline,
+			None => return self.transport.read_message().map_err(Error::from_receive),
+		};
+		let now = Instant::now();
+		if now >= deadline {
+			return Err(self.cancel_for_deadline());
+		}
+		match self.poll_message(deadline - now)? {
+			Some(msg) => Ok(msg),
+			None => Err(self.cancel_for_deadline()),
+		}
+	}
+
+	/// Read a message, or return None if the device didn't start sending one within the timeout.
+	/// The rest of the message is read within the deadline if one is set.
+	fn poll_message(&mut self, timeout: Duration) -> Result<Option<ProtoMessage>> {
+		match self.transport.poll_message(timeout, self.deadline) {
+			Ok(msg) => Ok(msg),
+			// The deadline passed while the message was being received.
+			Err(transport::error::Error::DeviceReadTimeout) if self.deadline.is_some() => {
+				Err(self.cancel_for_deadline())
+			}
+			Err(e) => Err(Error::from_receive(e)),
+		}
+	}
+
+	/// Run the flow with an overall deadline.  Every read from the device within the flow waits
+	/// at most until the deadline, so flows spanning many round-trips, like signing a
+	/// transaction, can be bounded by a single deadline.  When the deadline passes, the request
+	/// is cancelled on the device and an `Error::DeadlineExceeded` is returned.  Nested calls
+	/// can only shorten the deadline.
+	///
+	/// The flow must be completed within the closure, responses returned from it are no longer
+	/// bound by the deadline.
+	pub fn run_with_deadline<T, F>(&mut self, deadline: Instant, flow: F) -> Result<T>
+	where
+		F: FnOnce(&mut Trezor) -> Result<T>,
+	{
+		let previous = self.deadline;
+		self.deadline = Some(match previous {
+			Some(p) => cmp::min(p, deadline),
+			None => deadline,
+		});
+		let mut guard = DeadlineGuard {
+			client: self,
+			previous: previous,
+		};
+		flow(&mut *guard.client)
+	}
+
+	/// Cancel the current request on the device because the deadline passed.  The messages the
+	/// device still sends for the request are discarded.
+	fn cancel_for_deadline(&mut self) -> Error {
+		debug!("Deadline exceeded, cancelling");
+		match self.cancel_and_discard() {
+			Ok(()) => Error::DeadlineExceeded,
+			Err(e) => e,
+		}
+	}
+
+	/// Cancel the request the device might be busy with and discard the messages it still sends
+	/// for it.
+	fn cancel_and_discard(&mut self) -> Result<()> {
+		// The Cancel message has no fields, so its encoding is empty.
+		let proto_msg = ProtoMessage(MessageType_Cancel, Vec::new());
+		self.transport.write_message(proto_msg).map_err(|e| Error::TransportSendMessage(e))?;
+		// The device answers the cancelled request with a failure, possibly after the response
+		// it was already sending.
+		let until = Instant::now() + CANCEL_GRACE;
+		loop {
+			let now = Instant::now();
+			if now >= until {
+				warn!("No response to Cancel");
+				return Ok(());
+			}
+			match self.transport.poll_message(until - now, None) {
+				Ok(Some(ref msg)) if msg.message_type() == MessageType_Failure => return Ok(()),
+				Ok(Some(msg)) => trace!("Discarding {:?} msg after Cancel", msg.message_type()),
+				Ok(None) => {}
+				// The rest of a message that was cut off.
+				Err(transport::error::Error::DeviceBadMagic) => {
+					trace!("Discarding chunk after Cancel")
+				}
+				Err(e) => return Err(Error::from_receive(e)),
+			}
+		}
+	}
+
+	/// Bring the device back in a known state after an operation was aborted halfway, f.e. by a
+	/// panic: the request it might be busy with is cancelled and it is initialized again.
+	pub(crate) fn recover(&mut self) -> Result<()> {
+		self.cancel_and_discard()?;
+		self.init_device()
+	}
+
+	/// Set the channel over which progress events are sent, like the periodic
+	/// `ProgressEvent::WaitingForUser` events while the device waits for the user to confirm and
+	/// the `ProgressEvent::SigningInput` events while signing a transaction.  With
+	/// `with_pin_retries`, `ProgressEvent::DeviceBackoff` events are sent after invalid PINs.
+	pub fn set_progress_sender(&mut self, sender: Option<mpsc::Sender<ProgressEvent>>) {
+		self.progress_sender = sender;
+	}
+
+	/// Send a progress event if a progress channel is set.
+	pub(crate) fn send_progress(&self, event: ProgressEvent) {
+		if let Some(ref sender) = self.progress_sender {
+			// It's fine if the receiver is gone, the events are only informational.
+			let _ = sender.send(event);
+		}
+	}
+
+	/// Set how the payloads of the messages sent to and received from the device are logged at
+	/// the trace level.
+	pub fn set_payload_log_config(&mut self, config: PayloadLogConfig) {
+		self.payload_log = config;
+	}
+
+	/// Set the maximum time to wait for the user to confirm on the device.  When it's exceeded,
+	/// the request is cancelled on the device and an `Error::InteractionTimeout` is returned.  By
+	/// default there is no limit.
+	pub fn set_interaction_timeout(&mut self, timeout: Option<Duration>) {
+		self.interaction_timeout = timeout;
+	}
+
+	/// Read the next message while the device waits for the user, sending progress events and
+	/// enforcing the interaction timeout.
+	fn read_waiting_for_user(&mut self) -> Result<ProtoMessage> {
+		if self.progress_sender.is_none() && self.interaction_timeout.is_none() {
+			return self.read_message();
+		}
+
+		let start = Instant::now();
+		loop {
+			let elapsed = start.elapsed();
+			let mut poll_timeout = match self.interaction_timeout {
+				Some(timeout) if elapsed >= timeout => return self.cancel_interaction(timeout),
+				Some(timeout) => cmp::min(WAITING_FOR_USER_INTERVAL, timeout - elapsed),
+				None => WAITING_FOR_USER_INTERVAL,
+			};
+			if let Some(deadline) = self.deadline {
+				let now = Instant::now();
+				if now >= deadline {
+					return Err(self.cancel_for_deadline());
+				}
+				poll_timeout = cmp::min(poll_timeout, deadline - now);
+			}
+			let poll = self.poll_message(poll_timeout)?;
+			if let Some(msg) = poll {
+				return Ok(msg);
+			}
+			self.send_progress(ProgressEvent::WaitingForUser {
+				elapsed: start.elapsed(),
+			});
+		}
+	}
+
+	/// Cancel the request the device is waiting for the user for.
+	fn cancel_interaction(&mut self, timeout: Duration) -> Result<ProtoMessage> {
+		debug!("No user response within {:?}, cancelling", timeout);
+		self.cancel_request()?;
+		Err(Error::InteractionTimeout(timeout))
+	}
+
+	/// Cancel the interaction request the device is waiting for an answer to.
+	fn cancel_request(&mut self) -> Result<()> {
+		let req = protos::Cancel::new();
+		let proto_msg = ProtoMessage(MessageType_Cancel, req.write_to_bytes()?);
 		self.transport.write_message(proto_msg).map_err(|e| Error::TransportSendMessage(e))?;
-		self.transport.read_message().map_err(|e| Error::TransportReceiveMessage(e))
+		// The device answers the cancelled request with a failure.
+		let resp = self.transport.read_message().map_err(Error::from_receive)?;
+		if resp.message_type() != MessageType_Failure {
+			warn!("Unexpected response to Cancel: {:?}", resp.message_type());
+		}
+		Ok(())
 	}
 
 	/// Sends a message and returns a TrezorResponse with either the expected response message,
@@ -351,22 +911,60 @@ impl Trezor {
 		message: S,
 		result_handler: Box<ResultHandler<'a, T, R>>,
 	) -> Result<TrezorResponse<'a, T, R>> {
-		trace!("Sending {:?} msg: {:?}", S::message_type(), message);
-		let resp = self.call_raw(message)?;
+		let resume = self.resume_ack.borrow_mut().take();
+		let resp = match resume {
+			Some(resume) => {
+				if resume.request_type != S::message_type() {
+					// Keep the answer, so the pending interaction is restored.
+					*self.resume_ack.borrow_mut() = Some(resume);
+					return Err(Error::InvalidInteractionToken);
+				}
+				trace!(
+					"Resuming {:?} with {:?} msg",
+					resume.request_type,
+					resume.ack.message_type()
+				);
+				self.request_type = Some(resume.request_type);
+				self.signing_request = resume.interaction.signing_request;
+				self.call_proto(resume.ack)?
+			}
+			None => {
+				trace!(
+					"Sending {:?} msg: {}",
+					S::message_type(),
+					self.payload_log.describe_message(S::message_type(), &message)
+				);
+				if !is_interaction_ack(S::message_type()) {
+					self.request_type = Some(S::message_type());
+				}
+				self.call_raw(message)?
+			}
+		};
 		if resp.message_type() == R::message_type() {
-			let resp_msg = resp.into_message()?;
-			trace!("Received {:?} msg: {:?}", R::message_type(), resp_msg);
+			let resp_msg = if R::enforce_required_fields() {
+				resp.into_message()?
+			} else {
+				resp.into_message_lenient()?
+			};
+			trace!(
+				"Received {:?} msg: {}",
+				R::message_type(),
+				self.payload_log.describe_message(R::message_type(), &resp_msg)
+			);
 			Ok(TrezorResponse::Ok(result_handler(self, resp_msg)?))
 		} else {
 			match resp.message_type() {
 				MessageType_Failure => {
-					let fail_msg = resp.into_message()?;
+					let fail_msg: protos::Failure = resp.into_message()?;
 					debug!("Received failure: {:?}", fail_msg);
-					Ok(TrezorResponse::Failure(fail_msg))
+					Ok(TrezorResponse::Failure(fail_msg.into()))
 				}
 				MessageType_ButtonRequest => {
 					let req_msg = resp.into_message()?;
 					trace!("Received ButtonRequest: {:?}", req_msg);
+					if self.resumable_interactions {
+						return Err(self.suspend(InteractionType::Button));
+					}
 					Ok(TrezorResponse::ButtonRequest(ButtonRequest {
 						message: req_msg,
 						client: self,
@@ -376,6 +974,9 @@ impl Trezor {
 				MessageType_PinMatrixRequest => {
 					let req_msg = resp.into_message()?;
 					trace!("Received PinMatrixRequest: {:?}", req_msg);
+					if self.resumable_interactions {
+						return Err(self.suspend(InteractionType::PinMatrix));
+					}
 					Ok(TrezorResponse::PinMatrixRequest(PinMatrixRequest {
 						message: req_msg,
 						client: self,
@@ -385,6 +986,9 @@ impl Trezor {
 				MessageType_PassphraseRequest => {
 					let req_msg = resp.into_message()?;
 					trace!("Received PassphraseRequest: {:?}", req_msg);
+					if self.resumable_interactions {
+						return Err(self.suspend(InteractionType::Passphrase));
+					}
 					Ok(TrezorResponse::PassphraseRequest(PassphraseRequest {
 						message: req_msg,
 						client: self,
@@ -392,8 +996,13 @@ impl Trezor {
 					}))
 				}
 				MessageType_PassphraseStateRequest => {
-					let req_msg = resp.into_message()?;
+					let req_msg: protos::PassphraseStateRequest = resp.into_message()?;
 					trace!("Received PassphraseStateRequest: {:?}", req_msg);
+					self.passphrase_state = Some(req_msg.get_state().to_vec());
+					if self.resumable_interactions {
+						// No user interaction needed, so just ack right away.
+						return self.call(protos::PassphraseStateAck::new(), result_handler);
+					}
 					Ok(TrezorResponse::PassphraseStateRequest(PassphraseStateRequest {
 						message: req_msg,
 						client: self,
@@ -404,24 +1013,212 @@ impl Trezor {
 					debug!(
 						"Received unexpected msg type: {:?}; raw msg: {}",
 						mtype,
-						hex::encode(resp.into_payload())
+						self.payload_log.describe_payload(mtype, resp.payload())
 					);
-					Err(Error::UnexpectedMessageType(mtype))
+					Err(Error::UnexpectedMessageType(mtype.into()))
+				}
+			}
+		}
+	}
+
+	/// Enable or disable resumable interactions.  When enabled, interaction requests from the
+	/// device are not returned in the `TrezorResponse`, but as an `Error::InteractionRequired`
+	/// error with a token.  The operation can then be resumed later with `resume()`, which is
+	/// useful for applications that can't block while waiting for the user, like web servers.
+	pub fn set_resumable_interactions(&mut self, enabled: bool) {
+		self.resumable_interactions = enabled;
+	}
+
+	/// Register the pending interaction request and create the error to return for it.
+	fn suspend(&mut self, kind: InteractionType) -> Error {
+		let token = InteractionToken(self.next_interaction_token);
+		self.next_interaction_token += 1;
+		debug!("Suspending {:?} for {:?} interaction", self.request_type, kind);
+		*self.pending_interaction.borrow_mut() = Some(PendingInteraction {
+			token: token,
+			kind: kind.clone(),
+			request_type: self.request_type,
+			signing_request: self.signing_request.take(),
+		});
+		Error::InteractionRequired {
+			kind: kind,
+			token: token,
+		}
+	}
+
+	/// Resume an operation that returned an `Error::InteractionRequired` error by answering the
+	/// interaction request.  The operation must be passed again in `op` so that the response can
+	/// be processed.  Instead of sending the request again, the answer is sent to the device.
+	///
+	/// Only the message that started an operation can be resumed, so multi-step flows like
+	/// `sign_tx` can't be resumed halfway.  If `op` returns without sending the answer, f.e.
+	/// because it failed before anything was sent, the interaction stays pending and can be
+	/// resumed again with the same token.  The resumed operation is recorded in the audit log
+	/// again.
+	///
+	/// ```ignore
+	/// let address = match trezor.get_address(&path, script_type, network, true) {
+	/// 	Err(Error::InteractionRequired { token, .. }) => {
+	/// 		// Later, after asking the user.
+	/// 		trezor.resume(token, InteractionAnswer::Button, |t| {
+	/// 			t.get_address(&path, script_type, network, true)
+	/// 		})?.ok()?
+	/// 	}
+	/// 	res => res?.ok()?,
+	/// };
+	/// ```
+	pub fn resume<'a, T, R, F>(
+		&'a mut self,
+		token: InteractionToken,
+		answer: InteractionAnswer,
+		op: F,
+	) -> Result<TrezorResponse<'a, T, R>>
+	where
+		R: TrezorMessage,
+		F: FnOnce(&'a mut Trezor) -> Result<TrezorResponse<'a, T, R>>,
+	{
+		let pending =
+			self.pending_interaction.borrow_mut().take().ok_or(Error::InvalidInteractionToken)?;
+		if pending.token != token {
+			*self.pending_interaction.borrow_mut() = Some(pending);
+			return Err(Error::InvalidInteractionToken);
+		}
+		let ack = match (pending.kind.clone(), answer) {
+			(InteractionType::Button, InteractionAnswer::Button) => {
+				let req = protos::ButtonAck::new();
+				ProtoMessage(MessageType_ButtonAck, req.write_to_bytes()?)
+			}
+			(InteractionType::PinMatrix, InteractionAnswer::Pin(pin)) => {
+				let mut req = protos::PinMatrixAck::new();
+				req.set_pin(pin);
+				ProtoMessage(MessageType_PinMatrixAck, req.write_to_bytes()?)
+			}
+			(InteractionType::Passphrase, InteractionAnswer::Passphrase(passphrase)) => {
+				let mut req = protos::PassphraseAck::new();
+				if let Some(passphrase) = passphrase {
+					req.set_passphrase(passphrase);
+				}
+				ProtoMessage(MessageType_PassphraseAck, req.write_to_bytes()?)
+			}
+			(kind, _) => {
+				*self.pending_interaction.borrow_mut() = Some(pending);
+				return Err(Error::InvalidInteractionAnswer(kind));
+			}
+		};
+		let request_type = pending.request_type.ok_or(Error::InvalidInteractionToken)?;
+		*self.resume_ack.borrow_mut() = Some(ResumeAck {
+			request_type: request_type,
+			interaction: pending,
+			ack: ack,
+		});
+		let _guard = ResumeGuard {
+			pending_interaction: self.pending_interaction.clone(),
+			resume_ack: self.resume_ack.clone(),
+		};
+		op(self)
+	}
+
+	/// Perform an operation and resolve its interaction requests with the given handler.  When
+	/// the device reports an invalid PIN, the operation is retried up to `retries` times.  Before
+	/// every retry, the handler is notified with the number of invalid PINs entered so far.
+	///
+	/// After invalid PINs, the device delays checking the next PIN.  The device doesn't report
+	/// the delay, so it is derived from the invalid PINs entered in this operation, passed to the
+	/// handler and sent as a `ProgressEvent::DeviceBackoff` before retrying.  The device also
+	/// counts invalid PINs entered before, so the actual delay can be longer.
+	pub fn with_pin_retries<T, R, H, F>(
+		&mut self,
+		retries: usize,
+		handler: &mut H,
+		mut op: F,
+	) -> Result<T>
+	where
+		R: TrezorMessage,
+		H: InteractionHandler + ?Sized,
+		F: for<'a> FnMut(&'a mut Trezor) -> Result<TrezorResponse<'a, T, R>>,
+	{
+		let mut retry = 0;
+		let mut failed_attempts = 0;
+		loop {
+			match op(self)?.interact(handler) {
+				Err(Error::FailureResponse(ref f))
+					if retry < retries
+						&& (f.code() == Some(FailureCode::PinInvalid)
+							|| f.code() == Some(FailureCode::PinExpected)) =>
+				{
+					retry += 1;
+					if f.code() == Some(FailureCode::PinInvalid) {
+						failed_attempts += 1;
+					}
+					debug!("PIN failure, retrying ({}/{}): {}", retry, retries, f);
+					let backoff = interaction::pin_backoff(failed_attempts);
+					handler.pin_invalid(&PinRetry {
+						retry: retry,
+						retries_left: retries - retry,
+						failed_attempts: failed_attempts,
+						backoff: backoff,
+						message: f.message().to_owned(),
+					});
+					if backoff > Duration::from_secs(0) {
+						self.send_progress(ProgressEvent::DeviceBackoff {
+							wait: backoff,
+						});
+					}
 				}
+				res => return res,
 			}
 		}
 	}
 
+	/// Check that the device was initialized with `init_device()` or `initialize()`.  Most calls
+	/// fail on the device or behave unexpectedly without an initialized session.
+	fn check_initialized(&self) -> Result<()> {
+		if self.features.is_none() {
+			return Err(Error::NotInitialized);
+		}
+		Ok(())
+	}
+
 	pub fn init_device(&mut self) -> Result<()> {
-		let features = self.initialize()?.ok()?;
-		self.features = Some(features);
+		self.initialize()?.ok()?;
+		Ok(())
+	}
+
+	/// Initialize the device and check that it is the device with the given device ID.  If
+	/// another device is connected, `Error::DeviceChanged` is returned and the device stays
+	/// uninitialized, so it can't be used by accident.  It can be called again at any time to
+	/// start a new session with the same device.
+	pub fn init_device_expecting(&mut self, device_id: &str) -> Result<()> {
+		self.init_device()?;
+		let actual = self.features.as_ref().and_then(|f| f.device_id()).map(|d| d.to_owned());
+		if actual.as_ref().map(|d| d.as_str()) != Some(device_id) {
+			self.features = None;
+			return Err(Error::DeviceChanged {
+				expected: device_id.to_owned(),
+				actual: actual,
+			});
+		}
 		Ok(())
 	}
 
-	pub fn initialize(&mut self) -> Result<TrezorResponse<Features, Features>> {
+	pub fn initialize(&mut self) -> Result<TrezorResponse<DeviceFeatures, protos::Features>> {
+		// Initializing starts a new session.
+		self.passphrase_state = None;
+		self.features = None;
+		*self.pending_interaction.borrow_mut() = None;
+		*self.resume_ack.borrow_mut() = None;
+		self.signing_request = None;
+		self.clear_cache();
 		let mut req = protos::Initialize::new();
 		req.set_state(Vec::new());
-		self.call(req, Box::new(|_, m| Ok(m)))
+		self.call(
+			req,
+			Box::new(|c, m| {
+				let features: DeviceFeatures = m.into();
+				c.features = Some(features.clone());
+				Ok(features)
+			}),
+		)
 	}
 
 	pub fn ping(&mut self, message: &str) -> Result<TrezorResponse<(), protos::Success>> {
@@ -431,16 +1228,96 @@ impl Trezor {
 	}
 
 	pub fn change_pin(&mut self, remove: bool) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
 		let mut req = protos::ChangePin::new();
 		req.set_remove(remove);
-		self.call(req, Box::new(|_, _| Ok(())))
+		self.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(AuditEvent::PinChanged {
+					remove: remove,
+				});
+				Ok(())
+			}),
+		)
 	}
 
+	/// Wipe the device.  After the wipe, the device has to be initialized again with
+	/// `init_device()`.
 	pub fn wipe_device(&mut self) -> Result<TrezorResponse<(), protos::Success>> {
+		self.wipe_device_then(|_| Ok(()))
+	}
+
+	/// Wipe the device and distinguish the confirmation request of the device.  After the wipe,
+	/// all state of the client about the device is cleared.  With `reinitialize`, the device is
+	/// initialized again right after the wipe and the new features are returned.  Other
+	/// interaction requests are cancelled and returned as an error.
+	pub fn wipe_device_flow(&mut self, reinitialize: bool) -> Result<WipeDeviceResponse> {
+		let resp = self.wipe_device_then(move |c| {
+			if reinitialize {
+				c.init_device()?;
+				Ok(c.features.clone())
+			} else {
+				Ok(None)
+			}
+		})?;
+		let kind = match resp {
+			TrezorResponse::Ok(features) => return Ok(WipeDeviceResponse::Wiped(features)),
+			TrezorResponse::Failure(m) => return Err(Error::FailureResponse(m)),
+			TrezorResponse::ButtonRequest(r) => {
+				if r.request_type() == ButtonRequestType::WipeDevice {
+					let confirmation = WipeDeviceConfirmation::new(r);
+					return Ok(WipeDeviceResponse::ConfirmationRequired(confirmation));
+				}
+				r.client.cancel_request()?;
+				InteractionType::Button
+			}
+			TrezorResponse::PinMatrixRequest(r) => {
+				r.client.cancel_request()?;
+				InteractionType::PinMatrix
+			}
+			TrezorResponse::PassphraseRequest(r) => {
+				r.client.cancel_request()?;
+				InteractionType::Passphrase
+			}
+			TrezorResponse::PassphraseStateRequest(r) => {
+				r.client.cancel_request()?;
+				InteractionType::PassphraseState
+			}
+		};
+		Err(Error::UnexpectedInteractionRequest(kind))
+	}
+
+	/// Send the wipe request.  When the device confirms the wipe, all state about the device is
+	/// cleared before the given function is called.
+	fn wipe_device_then<'a, T, F>(
+		&'a mut self,
+		then: F,
+	) -> Result<TrezorResponse<'a, T, protos::Success>>
+	where
+		F: Fn(&'a mut Trezor) -> Result<T> + 'static,
+	{
+		self.check_initialized()?;
+		self.clear_cache();
 		let req = protos::WipeDevice::new();
-		self.call(req, Box::new(|_, _| Ok(())))
+		self.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(AuditEvent::DeviceWiped);
+				c.clear_device_state();
+				then(c)
+			}),
+		)
 	}
 
+	/// Clear all state about the device after it was wiped.
+	fn clear_device_state(&mut self) {
+		self.features = None;
+		self.passphrase_state = None;
+		self.clear_cache();
+	}
+
+	/// Recover a seed from an English mnemonic on the device.
 	pub fn recover_device(
 		&mut self,
 		word_count: WordCount,
@@ -449,8 +1326,36 @@ impl Trezor {
 		label: String,
 		dry_run: bool,
 	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.recover_device_with_options(
+			word_count,
+			passphrase_protection,
+			pin_protection,
+			label,
+			dry_run,
+			MnemonicOptions::default(),
+		)
+	}
+
+	/// Recover a seed on the device.  The mnemonic is in the language of the options, which must
+	/// be supported by the firmware.  Use the `wordlist` module to validate the words on the host.
+	pub fn recover_device_with_options(
+		&mut self,
+		word_count: WordCount,
+		passphrase_protection: bool,
+		pin_protection: bool,
+		label: String,
+		dry_run: bool,
+		options: MnemonicOptions,
+	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		let language = options.language;
+		if !language.supported_by_firmware() {
+			return Err(Error::UnsupportedLanguage(language));
+		}
+		self.clear_cache();
+		let word_count = word_count as u32;
 		let mut req = protos::RecoveryDevice::new();
-		req.set_word_count(word_count as u32);
+		req.set_word_count(word_count);
 		req.set_passphrase_protection(passphrase_protection);
 		req.set_pin_protection(pin_protection);
 		req.set_label(label);
@@ -459,9 +1364,50 @@ impl Trezor {
 		req.set_field_type(
 			protos::RecoveryDevice_RecoveryDeviceType::RecoveryDeviceType_ScrambledWords,
 		);
-		//TODO(stevenroose) support languages
-		req.set_language("english".to_owned());
-		self.call(req, Box::new(|_, _| Ok(())))
+		req.set_language(language.name().to_owned());
+		self.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(AuditEvent::DeviceRecovered {
+					word_count: word_count as usize,
+					dry_run: dry_run,
+				});
+				Ok(())
+			}),
+		)
+	}
+
+	/// Load a seed onto the device from a mnemonic.  This is only supported by debug firmware and
+	/// the emulator and requires the device to be wiped first.
+	pub fn load_device(
+		&mut self,
+		mnemonic: String,
+		pin: Option<String>,
+		passphrase_protection: bool,
+		label: String,
+		skip_checksum: bool,
+	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		self.clear_cache();
+		let event = AuditEvent::DeviceLoaded {
+			passphrase_protection: passphrase_protection,
+			pin_protection: pin.is_some(),
+		};
+		let mut req = protos::LoadDevice::new();
+		req.set_mnemonic(mnemonic);
+		if let Some(pin) = pin {
+			req.set_pin(pin);
+		}
+		req.set_passphrase_protection(passphrase_protection);
+		req.set_label(label);
+		req.set_skip_checksum(skip_checksum);
+		self.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(event.clone());
+				Ok(())
+			}),
+		)
 	}
 
 	pub fn reset_device(
@@ -474,25 +1420,66 @@ impl Trezor {
 		skip_backup: bool,
 		no_backup: bool,
 	) -> Result<TrezorResponse<EntropyRequest, protos::EntropyRequest>> {
+		self.reset_device_with_options(
+			display_random,
+			strength,
+			passphrase_protection,
+			pin_protection,
+			label,
+			skip_backup,
+			no_backup,
+			MnemonicOptions::default(),
+		)
+	}
+
+	/// Create a new seed on the device with a mnemonic in the language of the options, which
+	/// must be supported by the firmware.
+	pub fn reset_device_with_options(
+		&mut self,
+		display_random: bool,
+		strength: usize,
+		passphrase_protection: bool,
+		pin_protection: bool,
+		label: String,
+		skip_backup: bool,
+		no_backup: bool,
+		options: MnemonicOptions,
+	) -> Result<TrezorResponse<EntropyRequest, protos::EntropyRequest>> {
+		self.check_initialized()?;
+		let language = options.language;
+		if !language.supported_by_firmware() {
+			return Err(Error::UnsupportedLanguage(language));
+		}
+		self.clear_cache();
+		let event = AuditEvent::DeviceReset {
+			strength: strength,
+			passphrase_protection: passphrase_protection,
+			pin_protection: pin_protection,
+		};
 		let mut req = protos::ResetDevice::new();
 		req.set_display_random(display_random);
 		req.set_strength(strength as u32);
 		req.set_passphrase_protection(passphrase_protection);
 		req.set_pin_protection(pin_protection);
 		req.set_label(label);
+		req.set_language(language.name().to_owned());
 		req.set_skip_backup(skip_backup);
 		req.set_no_backup(no_backup);
 		self.call(
 			req,
-			Box::new(|c, _| {
+			Box::new(move |c, _| {
 				Ok(EntropyRequest {
 					client: c,
+					strength: strength,
+					event: event.clone(),
 				})
 			}),
 		)
 	}
 
 	pub fn backup(&mut self) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		self.audit(AuditEvent::BackupStarted);
 		let req = protos::BackupDevice::new();
 		self.call(req, Box::new(|_, _| Ok(())))
 	}
@@ -504,9 +1491,21 @@ impl Trezor {
 		label: Option<String>,
 		use_passphrase: Option<bool>,
 		homescreen: Option<Vec<u8>>,
-		passphrase_source: Option<PassphraseSource>,
+		passphrase_mode: Option<PassphraseMode>,
 		auto_lock_delay_ms: Option<usize>,
 	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		if self.model == Model::Trezor1 && passphrase_mode == Some(PassphraseMode::Device) {
+			return Err(Error::UnsupportedFeature("passphrase entry on the device"));
+		}
+		self.clear_cache();
+		let event = AuditEvent::SettingsChanged {
+			label: label.clone(),
+			use_passphrase: use_passphrase,
+			homescreen_changed: homescreen.is_some(),
+			passphrase_mode: passphrase_mode,
+			auto_lock_delay_ms: auto_lock_delay_ms,
+		};
 		let mut req = protos::ApplySettings::new();
 		if let Some(label) = label {
 			req.set_label(label);
@@ -517,13 +1516,36 @@ impl Trezor {
 		if let Some(homescreen) = homescreen {
 			req.set_homescreen(homescreen);
 		}
-		if let Some(passphrase_source) = passphrase_source {
-			req.set_passphrase_source(passphrase_source);
+		// The Trezor One only supports passphrase entry on the host, so there is nothing to set.
+		if let Some(passphrase_mode) = passphrase_mode.filter(|_| self.model != Model::Trezor1) {
+			let always_on_device = passphrase_mode == PassphraseMode::Device;
+			// Our protos predate the passphrase_always_on_device field, so we set it manually.
+			match self.uses_passphrase_always_on_device() {
+				Some(true) => req.mut_unknown_fields().add_varint(
+					APPLY_SETTINGS_PASSPHRASE_ALWAYS_ON_DEVICE,
+					always_on_device as u64,
+				),
+				Some(false) => req.set_passphrase_source(passphrase_mode.into()),
+				None => {
+					// Firmware unknown, older firmware ignores the new field and vice versa.
+					req.mut_unknown_fields().add_varint(
+						APPLY_SETTINGS_PASSPHRASE_ALWAYS_ON_DEVICE,
+						always_on_device as u64,
+					);
+					req.set_passphrase_source(passphrase_mode.into());
+				}
+			}
 		}
 		if let Some(auto_lock_delay_ms) = auto_lock_delay_ms {
 			req.set_auto_lock_delay_ms(auto_lock_delay_ms as u32);
 		}
-		self.call(req, Box::new(|_, _| Ok(())))
+		self.call(
+			req,
+			Box::new(move |c, _| {
+				c.audit(event.clone());
+				Ok(())
+			}),
+		)
 	}
 
 	pub fn get_public_key(
@@ -533,12 +1555,143 @@ impl Trezor {
 		network: Network,
 		show_display: bool,
 	) -> Result<TrezorResponse<bip32::ExtendedPubKey, protos::PublicKey>> {
+		self.check_initialized()?;
+		let address_n = utils::convert_path(&path);
+		let coin_name = utils::coin_name(network)?;
+		// When resuming, the device is waiting for the answer and can't be bypassed.
+		if !show_display && self.resume_ack.borrow().is_none() {
+			let key = self.cache_key(address_n.clone(), script_type, coin_name.clone());
+			if let Some(xpub) = key.as_ref().and_then(|k| self.cache.as_ref()?.xpub(k)) {
+				trace!("Using cached xpub for {:?}", key);
+				return Ok(TrezorResponse::Ok(xpub));
+			}
+		}
+
+		self.audit(AuditEvent::PublicKeyRequested {
+			path: address_n.clone(),
+			script_type: script_type,
+			coin_name: coin_name.clone(),
+			show_display: show_display,
+		});
 		let mut req = protos::GetPublicKey::new();
-		req.set_address_n(utils::convert_path(&path));
+		req.set_address_n(address_n.clone());
 		req.set_show_display(show_display);
-		req.set_coin_name(utils::coin_name(network)?);
-		req.set_script_type(script_type);
-		self.call(req, Box::new(|_, m| Ok(m.get_xpub().parse()?)))
+		req.set_coin_name(coin_name.clone());
+		req.set_script_type(script_type.into());
+		self.call(
+			req,
+			Box::new(move |c, m| {
+				let xpub: bip32::ExtendedPubKey = m.get_xpub().parse()?;
+				let key = c.cache_key(address_n.clone(), script_type, coin_name.clone());
+				if let (Some(key), Some(cache)) = (key, c.cache.as_mut()) {
+					cache.insert_xpub(key, xpub);
+				}
+				Ok(xpub)
+			}),
+		)
+	}
+
+	/// Check whether the address belongs to the device by comparing it to the addresses of the
+	/// candidate paths.  Returns the path of the address if found.  Only single-key addresses are
+	/// supported; P2SH addresses are assumed to be nested segwit.  The candidate addresses are
+	/// derived on the host from the xpub of their hardened parent, which is requested once per
+	/// parent; only candidates without a hardened index are requested from the device.  The
+	/// interaction requests for retrieving the keys are resolved with the handler.
+	pub fn check_address_ownership<H: InteractionHandler + ?Sized>(
+		&mut self,
+		address: &Address,
+		candidate_paths: &[bip32::DerivationPath],
+		handler: &mut H,
+	) -> Result<Option<bip32::DerivationPath>> {
+		let (script_type, host_address): (_, fn(&bip32::ExtendedPubKey, Network) -> Address) =
+			match utils::address_type(address) {
+				Some(AddressType::P2pkh) => {
+					(InputScriptType::SpendAddress, |x, n| Address::p2pkh(&x.public_key, n))
+				}
+				Some(AddressType::P2sh) => {
+					(InputScriptType::SpendP2shWitness, |x, n| Address::p2shwpkh(&x.public_key, n))
+				}
+				Some(AddressType::P2wpkh) => {
+					(InputScriptType::SpendWitness, |x, n| Address::p2wpkh(&x.public_key, n))
+				}
+				Some(AddressType::P2wsh) | None => return Ok(None),
+			};
+		let network = address.network;
+		let secp = secp256k1::Secp256k1::verification_only();
+		let mut parents: Vec<(Vec<u32>, bip32::ExtendedPubKey)> = Vec::new();
+		for path in candidate_paths {
+			let address_n = utils::convert_path(path);
+			// Only the indices after the last hardened index can be derived on the host.
+			let split =
+				address_n.iter().rposition(|i| i & utils::HARDENED != 0).map_or(0, |p| p + 1);
+			let mut derived = None;
+			if split > 0 {
+				let (parent_n, child_n) = address_n.split_at(split);
+				let known = parents.iter().find(|p| p.0 == parent_n).map(|p| p.1);
+				let parent = match known {
+					Some(xpub) => xpub,
+					None => {
+						let parent_path: bip32::DerivationPath = parent_n
+							.iter()
+							.map(|i| bip32::ChildNumber::from(*i))
+							.collect::<Vec<_>>()
+							.into();
+						let xpub = self
+							.get_public_key(&parent_path, script_type, network, false)?
+							.interact(handler)?;
+						parents.push((parent_n.to_vec(), xpub));
+						xpub
+					}
+				};
+				derived = child_n
+					.iter()
+					.try_fold(parent, |x, i| x.ckd_pub(&secp, bip32::ChildNumber::from(*i)))
+					.ok()
+					.map(|x| host_address(&x, network));
+			}
+			let candidate = match derived {
+				Some(candidate) => candidate,
+				None => self.get_address(path, script_type, network, false)?.interact(handler)?,
+			};
+			if candidate == *address {
+				return Ok(Some(path.clone()));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Export an account to an Electrum wallet file.  The account is derived at the BIP-44 style
+	/// path for the script type, like m/84'/0'/0' for the first native segwit account.  The
+	/// interaction requests for retrieving the public keys are resolved with the handler.
+	pub fn export_electrum_wallet<H: InteractionHandler + ?Sized>(
+		&mut self,
+		script_type: InputScriptType,
+		network: Network,
+		account: u32,
+		handler: &mut H,
+	) -> Result<String> {
+		let path = export::account_path(script_type, network, account)?;
+		let account_path: bip32::DerivationPath =
+			path.iter().map(|i| bip32::ChildNumber::from(*i)).collect::<Vec<_>>().into();
+		// The root fingerprint is the parent fingerprint of the purpose key, so the master key
+		// doesn't have to be requested from the device.
+		let purpose_path: bip32::DerivationPath = vec![bip32::ChildNumber::from(path[0])].into();
+
+		let purpose = self
+			.get_public_key(&purpose_path, InputScriptType::SpendAddress, network, false)?
+			.interact(handler)?;
+		let xpub =
+			self.get_public_key(&account_path, script_type, network, false)?.interact(handler)?;
+		let features = self.features.as_ref().ok_or(Error::NotInitialized)?;
+		ElectrumAccount {
+			xpub: xpub,
+			path: path,
+			script_type: script_type,
+			root_fingerprint: purpose.parent_fingerprint,
+			label: features.label().map(|l| l.to_owned()),
+			device_id: features.device_id().map(|d| d.to_owned()),
+		}
+		.to_wallet_file()
 	}
 
 	//TODO(stevenroose) multisig
@@ -549,12 +1702,41 @@ impl Trezor {
 		network: Network,
 		show_display: bool,
 	) -> Result<TrezorResponse<Address, protos::Address>> {
+		self.check_initialized()?;
+		let address_n = utils::convert_path(&path);
+		let coin_name = utils::coin_name(network)?;
+		// When resuming, the device is waiting for the answer and can't be bypassed.
+		if !show_display && self.resume_ack.borrow().is_none() {
+			let key = self.cache_key(address_n.clone(), script_type, coin_name.clone());
+			if let Some(address) = key.as_ref().and_then(|k| self.cache.as_ref()?.address(k)) {
+				trace!("Using cached address for {:?}", key);
+				return Ok(TrezorResponse::Ok(address));
+			}
+		}
+
 		let mut req = protos::GetAddress::new();
-		req.set_address_n(utils::convert_path(&path));
-		req.set_coin_name(utils::coin_name(network)?);
+		req.set_address_n(address_n.clone());
+		req.set_coin_name(coin_name.clone());
 		req.set_show_display(show_display);
-		req.set_script_type(script_type);
-		self.call(req, Box::new(|_, m| Ok(m.get_address().parse()?)))
+		req.set_script_type(script_type.into());
+		self.call(
+			req,
+			Box::new(move |c, m| {
+				let address: Address = m.get_address().parse()?;
+				c.audit(AuditEvent::AddressRetrieved {
+					path: address_n.clone(),
+					script_type: script_type,
+					coin_name: coin_name.clone(),
+					show_display: show_display,
+					address: address.to_string(),
+				});
+				let key = c.cache_key(address_n.clone(), script_type, coin_name.clone());
+				if let (Some(key), Some(cache)) = (key, c.cache.as_mut()) {
+					cache.insert_address(key, address.clone());
+				}
+				Ok(address)
+			}),
+		)
 	}
 
 	pub fn sign_tx(
@@ -562,16 +1744,78 @@ impl Trezor {
 		psbt: &psbt::PartiallySignedTransaction,
 		network: Network,
 	) -> Result<TrezorResponse<SignTxProgress, protos::TxRequest>> {
+		self.sign_tx_with_codec(
+			psbt,
+			&utils::BitcoinCodec {
+				network: network,
+			},
+		)
+	}
+
+	/// Sign a transaction of a Bitcoin-family coin that encodes its addresses with the given
+	/// codec.  Use `SignTxProgress::ack_psbt_with_codec` with the same codec to continue.
+	pub fn sign_tx_with_codec(
+		&mut self,
+		psbt: &psbt::PartiallySignedTransaction,
+		codec: &utils::AddressCodec,
+	) -> Result<TrezorResponse<SignTxProgress, protos::TxRequest>> {
+		self.check_initialized()?;
 		let tx = &psbt.global.unsigned_tx;
+		let coin_name = codec.coin_name()?;
+		// The acks of the flow are checked against the request checked by the policy.
+		let checked = if self.signing_policy.is_some() {
+			let request = SigningRequest::from_psbt(psbt, codec)?;
+			self.check_policy(request.clone())?;
+			Some(request)
+		} else {
+			None
+		};
+		if self.audit_sink.is_some() {
+			let inputs = tx
+				.input
+				.iter()
+				.map(|i| format!("{}:{}", i.previous_output.txid, i.previous_output.vout))
+				.collect();
+			let outputs = tx
+				.output
+				.iter()
+				.enumerate()
+				.map(|(idx, o)| AuditOutput {
+					destination: match codec.address_from_script(&o.script_pubkey) {
+						Some(addr) => addr,
+						None => hex::encode(o.script_pubkey.as_bytes()),
+					},
+					amount: o.value,
+					change_path: psbt
+						.outputs
+						.get(idx)
+						.filter(|o| o.hd_keypaths.len() == 1)
+						.and_then(|o| o.hd_keypaths.values().next())
+						.map(|v| utils::convert_path(&v.1)),
+				})
+				.collect();
+			self.audit(AuditEvent::TransactionSigningStarted {
+				coin_name: coin_name.clone(),
+				inputs: inputs,
+				outputs: outputs,
+			});
+		}
+
 		let mut req = protos::SignTx::new();
 		req.set_inputs_count(tx.input.len() as u32);
 		req.set_outputs_count(tx.output.len() as u32);
-		req.set_coin_name(utils::coin_name(network)?);
+		req.set_coin_name(coin_name);
 		req.set_version(tx.version);
 		req.set_lock_time(tx.lock_time);
-		self.call(req, Box::new(|c, m| Ok(SignTxProgress::new(c, m))))
+		self.call(req, Box::new(move |c, m| SignTxProgress::new(c, m, checked.clone())))
 	}
 
+	/// Sign a message with the key at the given path.
+	///
+	/// The message is normalized to Unicode NFC before it is sent to the device so that visually
+	/// identical messages result in the same signature.  The device itself doesn't normalize the
+	/// message, it signs the exact bytes it receives.  Use `sign_message_bytes` to sign messages
+	/// without normalization, f.e. to be compatible with software that signs raw bytes.
 	pub fn sign_message(
 		&mut self,
 		message: String,
@@ -580,13 +1824,43 @@ impl Trezor {
 		network: Network,
 	) -> Result<TrezorResponse<(Address, secp256k1::RecoverableSignature), protos::MessageSignature>>
 	{
-		let mut req = protos::SignMessage::new();
-		req.set_address_n(utils::convert_path(&path));
 		// Normalize to Unicode NFC.
 		let msg_bytes = message.nfc().collect::<String>().into_bytes();
-		req.set_message(msg_bytes);
-		req.set_coin_name(utils::coin_name(network)?);
-		req.set_script_type(script_type);
+		self.sign_message_bytes(msg_bytes, path, script_type, network)
+	}
+
+	/// Sign a message with the key at the given path without any normalization.
+	///
+	/// The device signs the exact bytes it receives.  It shows the message on the display if it
+	/// is valid UTF-8 and shows it hex-encoded otherwise.
+	pub fn sign_message_bytes(
+		&mut self,
+		message: Vec<u8>,
+		path: &bip32::DerivationPath,
+		script_type: InputScriptType,
+		network: Network,
+	) -> Result<TrezorResponse<(Address, secp256k1::RecoverableSignature), protos::MessageSignature>>
+	{
+		self.check_initialized()?;
+		let mut req = protos::SignMessage::new();
+		req.set_address_n(utils::convert_path(&path));
+		let coin_name = utils::coin_name(network)?;
+		if self.signing_policy.is_some() {
+			self.check_policy(SigningRequest::Message {
+				path: utils::convert_path(&path),
+				script_type: script_type,
+				network: network,
+				message: message.clone(),
+			})?;
+		}
+		self.audit(AuditEvent::MessageSigningStarted {
+			path: utils::convert_path(&path),
+			coin_name: coin_name.clone(),
+			message: message.clone(),
+		});
+		req.set_message(message);
+		req.set_coin_name(coin_name);
+		req.set_script_type(script_type.into());
 		self.call(
 			req,
 			Box::new(|_, m| {
@@ -596,4 +1870,185 @@ impl Trezor {
 			}),
 		)
 	}
+
+	/// Verify the signature of a message by the given address on the device.  The device shows
+	/// the address and the message if the signature is valid.  An invalid signature results in a
+	/// failure response.
+	pub fn verify_message(
+		&mut self,
+		address: &Address,
+		signature: &[u8],
+		message: Vec<u8>,
+	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		let mut req = protos::VerifyMessage::new();
+		req.set_address(address.to_string());
+		req.set_signature(signature.to_vec());
+		req.set_message(message);
+		req.set_coin_name(utils::coin_name(address.network)?);
+		self.call(req, Box::new(|_, _| Ok(())))
+	}
+
+	/// Get the Ethereum address for the given path.  The address is returned as a hex string with
+	/// the 0x prefix, checksummed if the device provides it that way.
+	///
+	/// With `chunkify`, the device shows the address split into chunks for easier verification.
+	/// The `encoded_network` is a signed network definition that lets the device show the name of
+	/// the chain the address is used on.  Both are ignored by firmware that doesn't support them.
+	pub fn ethereum_get_address(
+		&mut self,
+		path: &bip32::DerivationPath,
+		show_display: bool,
+		chunkify: bool,
+		encoded_network: Option<Vec<u8>>,
+	) -> Result<TrezorResponse<String, protos::EthereumAddress>> {
+		self.check_initialized()?;
+		let mut req = protos::EthereumGetAddress::new();
+		req.set_address_n(utils::convert_path(&path));
+		req.set_show_display(show_display);
+		// Our protos predate these fields, so we set them manually.
+		if let Some(encoded_network) = encoded_network {
+			req.mut_unknown_fields()
+				.add_length_delimited(ETHEREUM_GET_ADDRESS_ENCODED_NETWORK, encoded_network);
+		}
+		if chunkify {
+			req.mut_unknown_fields().add_varint(ETHEREUM_GET_ADDRESS_CHUNKIFY, 1);
+		}
+		self.call(
+			req,
+			Box::new(|_, m| {
+				let address = m
+					.get_unknown_fields()
+					.get(ETHEREUM_ADDRESS_STRING)
+					.and_then(|v| v.length_delimited.first())
+					.and_then(|a| String::from_utf8(a.clone()).ok());
+				match address {
+					Some(address) => Ok(address),
+					None if m.has_address() => Ok(format!("0x{}", hex::encode(m.get_address()))),
+					None => Err(Error::MalformedEthereumAddress),
+				}
+			}),
+		)
+	}
+
+	/// Verify the signature of a message by the given Ethereum address on the device.  The
+	/// address is a hex string with or without the 0x prefix.  The device shows the address and
+	/// the message if the signature is valid.  An invalid signature results in a failure response.
+	pub fn ethereum_verify_message(
+		&mut self,
+		address: &str,
+		signature: &[u8],
+		message: Vec<u8>,
+	) -> Result<TrezorResponse<(), protos::Success>> {
+		self.check_initialized()?;
+		let hex_address = address.trim_start_matches("0x");
+		let address_bytes = match hex::decode(hex_address) {
+			Ok(ref b) if b.len() == 20 => b.clone(),
+			_ => return Err(Error::InvalidAddress(address.to_owned())),
+		};
+		let mut req = protos::EthereumVerifyMessage::new();
+		req.set_address(address_bytes);
+		// Newer firmware expects the address as a string in a new field.
+		req.mut_unknown_fields().add_length_delimited(
+			ETHEREUM_VERIFY_MESSAGE_ADDRESS_STRING,
+			format!("0x{}", hex_address).into_bytes(),
+		);
+		req.set_signature(signature.to_vec());
+		req.set_message(message);
+		self.call(req, Box::new(|_, _| Ok(())))
+	}
+
+	/// Get the NEM address for the given path on the given network: 0x68 for mainnet, 0x98 for
+	/// testnet and 0x60 for Mijin.
+	pub fn nem_get_address(
+		&mut self,
+		path: &bip32::DerivationPath,
+		network: u8,
+		show_display: bool,
+	) -> Result<TrezorResponse<String, protos::NEMAddress>> {
+		self.check_initialized()?;
+		let mut req = protos::NEMGetAddress::new();
+		req.set_address_n(utils::convert_path(&path));
+		req.set_network(network as u32);
+		req.set_show_display(show_display);
+		self.call(req, Box::new(|_, m| Ok(m.get_address().to_owned())))
+	}
+
+	/// Get the Ripple address for the given path.
+	pub fn ripple_get_address(
+		&mut self,
+		path: &bip32::DerivationPath,
+		show_display: bool,
+	) -> Result<TrezorResponse<String, protos::RippleAddress>> {
+		self.check_initialized()?;
+		let mut req = protos::RippleGetAddress::new();
+		req.set_address_n(utils::convert_path(&path));
+		req.set_show_display(show_display);
+		self.call(req, Box::new(|_, m| Ok(m.get_address().to_owned())))
+	}
+
+	/// Get the Stellar address for the given path.  For compatibility with other wallets, the
+	/// path should be m/44'/148'/index'.
+	pub fn stellar_get_address(
+		&mut self,
+		path: &bip32::DerivationPath,
+		show_display: bool,
+	) -> Result<TrezorResponse<String, protos::StellarAddress>> {
+		self.check_initialized()?;
+		let mut req = protos::StellarGetAddress::new();
+		req.set_address_n(utils::convert_path(&path));
+		req.set_show_display(show_display);
+		self.call(req, Box::new(|_, m| Ok(m.get_address().to_owned())))
+	}
+
+	/// Get the address for the given path on any of the supported chains as a string.  This is
+	/// meant for generic code that shows addresses for verification.  The response types differ
+	/// per chain, so the interaction requests are resolved with the handler.
+	pub fn get_address_any<H: InteractionHandler + ?Sized>(
+		&mut self,
+		chain: Chain,
+		path: &bip32::DerivationPath,
+		options: AddressOptions,
+		handler: &mut H,
+	) -> Result<String> {
+		let show = options.show_display;
+		match chain {
+			Chain::Bitcoin(network, script_type) => Ok(self
+				.get_address(path, script_type, network, show)?
+				.interact(handler)?
+				.to_string()),
+			Chain::Ethereum => {
+				self.ethereum_get_address(path, show, options.chunkify, None)?.interact(handler)
+			}
+			Chain::Nem(network) => self.nem_get_address(path, network, show)?.interact(handler),
+			Chain::Ripple => self.ripple_get_address(path, show)?.interact(handler),
+			Chain::Stellar => self.stellar_get_address(path, show)?.interact(handler),
+		}
+	}
+
+	/// Sign a Stellar transaction with the key at the given path.  For compatibility with other
+	/// wallets, the path should be m/44'/148'/index'.
+	pub fn stellar_sign_tx(
+		&mut self,
+		path: &bip32::DerivationPath,
+		tx: &stellar::Transaction,
+	) -> Result<TrezorResponse<StellarSignTxProgress, protos::StellarTxOpRequest>> {
+		self.check_initialized()?;
+		if tx.operations.is_empty() {
+			return Err(Error::InvalidStellarTransaction("no operations".to_owned()));
+		}
+		if self.signing_policy.is_some() {
+			self.check_policy(SigningRequest::StellarTransaction {
+				path: utils::convert_path(&path),
+				tx: tx.clone(),
+			})?;
+		}
+
+		let req = tx.to_sign_tx(utils::convert_path(&path))?;
+		let operations = tx.operations.clone();
+		self.call(
+			req,
+			Box::new(move |c, _| Ok(StellarSignTxProgress::new(c, operations.clone(), 0))),
+		)
+	}
 }
@@ -3,6 +3,7 @@
 use std::error;
 use std::fmt;
 use std::result;
+use std::time::Duration;
 
 use bitcoin;
 use bitcoin::util::base58;
@@ -11,8 +12,10 @@ use protobuf::error::ProtobufError;
 use secp256k1;
 
 use client::InteractionType;
-use protos;
+use interaction::InteractionToken;
 use transport;
+use types::{Failure, InputScriptType, MessageKind};
+use wordlist::Language;
 
 /// Trezor error.
 #[derive(Debug)]
@@ -21,6 +24,17 @@ pub enum Error {
 	NoDeviceFound,
 	/// More than one device was plugged in.
 	DeviceNotUnique,
+	/// The device was used before it was initialized with `init_device()`.
+	NotInitialized,
+	/// The connected device is not the expected device.
+	DeviceChanged {
+		expected: String,
+		actual: Option<String>,
+	},
+	/// The device queue was shut down, its worker thread stopped or the operation panicked.
+	DeviceQueueClosed,
+	/// The emulator address configured in the environment is invalid.
+	InvalidEmulatorAddress(String),
 	/// Transport error connecting to device.
 	TransportConnect(transport::error::Error),
 	/// Transport error while beginning a session.
@@ -31,20 +45,56 @@ pub enum Error {
 	TransportSendMessage(transport::error::Error),
 	/// Transport error while receiving a message.
 	TransportReceiveMessage(transport::error::Error),
+	/// The device announced a message larger than the maximum message size, see
+	/// `Trezor::set_max_message_size()`.
+	MessageTooLarge(usize),
 	/// Received an unexpected message type from the device.
-	UnexpectedMessageType(protos::MessageType), //TODO(stevenroose) type alias
+	UnexpectedMessageType(MessageKind),
 	/// Error reading or writing protobuf messages.
-	Protobuf(ProtobufError),
+	Protobuf(CodecError),
 	/// A failure message was returned by the device.
-	FailureResponse(protos::Failure),
+	FailureResponse(Failure),
 	/// An unexpected interaction request was returned by the device.
 	UnexpectedInteractionRequest(InteractionType),
+	/// The device requests user interaction.  Resume the operation with `Trezor::resume` using
+	/// the token.  Only returned when resumable interactions are enabled.
+	InteractionRequired {
+		kind: InteractionType,
+		token: InteractionToken,
+	},
+	/// The user didn't respond on the device within the interaction timeout.  The request was
+	/// cancelled.
+	InteractionTimeout(Duration),
+	/// The deadline of the flow passed.  The request was cancelled.
+	DeadlineExceeded,
+	/// The interaction token doesn't belong to the pending interaction or the operation.
+	InvalidInteractionToken,
+	/// The answer doesn't match the kind of the pending interaction.
+	InvalidInteractionAnswer(InteractionType),
+	/// The given address is invalid.
+	InvalidAddress(String),
+	/// The signing policy denied the request for the given reason.
+	PolicyDenied(String),
 	/// Error in Base58 decoding
 	Base58(base58::Error),
+	/// The device doesn't support the given feature.
+	UnsupportedFeature(&'static str),
 	/// The given Bitcoin network is not supported.
 	UnsupportedNetwork,
+	/// The given script type is not supported for this operation.
+	UnsupportedScriptType(InputScriptType),
 	/// Provided entropy is not 32 bytes.
 	InvalidEntropy,
+	/// The seed strength is not a valid number of bits.
+	InvalidEntropyStrength(usize),
+	/// The mnemonic is invalid.
+	InvalidMnemonic(String),
+	/// The mnemonic language is not supported by the firmware.
+	UnsupportedLanguage(Language),
+	/// The seed entropy does not match the entropy derived from the device and host entropy.
+	EntropyCheckFailed,
+	/// The device returned a different value than the known-answer test vector.
+	TestVectorMismatch(String),
 	/// The device referenced a non-existing input or output index.
 	TxRequestInvalidIndex(usize),
 	/// The device referenced an unknown TXID.
@@ -52,18 +102,50 @@ pub enum Error {
 	/// The PSBT is missing the full tx for given input.
 	PsbtMissingInputTx(sha256d::Hash),
 	/// Device produced invalid TxRequest message.
-	MalformedTxRequest(protos::TxRequest),
+	MalformedTxRequest(String),
 	/// User provided invalid PSBT.
 	InvalidPsbt(String),
+	/// Device produced an EthereumAddress message without an address.
+	MalformedEthereumAddress,
+	/// User provided invalid Stellar transaction.
+	InvalidStellarTransaction(String),
 	/// Error encoding/decoding a Bitcoin data structure.
 	BitcoinEncode(bitcoin::consensus::encode::Error),
 	/// Elliptic curve crypto error.
 	Secp256k1(secp256k1::Error),
 }
 
+impl Error {
+	/// Wrap an error from receiving a message from the device.  Errors that users can act on are
+	/// returned as their own variant.
+	pub(crate) fn from_receive(e: transport::error::Error) -> Error {
+		match e {
+			transport::error::Error::MessageTooLarge(size) => Error::MessageTooLarge(size),
+			e => Error::TransportReceiveMessage(e),
+		}
+	}
+}
+
+/// An error encoding or decoding a message.  The error of the underlying protobuf library is
+/// not exposed, so that it can be upgraded without breaking changes.
+#[derive(Debug)]
+pub struct CodecError(ProtobufError);
+
+impl fmt::Display for CodecError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&self.0, f)
+	}
+}
+
+impl error::Error for CodecError {
+	fn description(&self) -> &str {
+		error::Error::description(&self.0)
+	}
+}
+
 impl From<ProtobufError> for Error {
 	fn from(e: ProtobufError) -> Error {
-		Error::Protobuf(e)
+		Error::Protobuf(CodecError(e))
 	}
 }
 
@@ -102,11 +184,18 @@ impl error::Error for Error {
 		match *self {
 			Error::NoDeviceFound => "Trezor device not found",
 			Error::DeviceNotUnique => "multiple Trezor devices found",
+			Error::NotInitialized => "the device was not initialized, call init_device() first",
+			Error::DeviceChanged {
+				..
+			} => "the connected device is not the expected device",
+			Error::DeviceQueueClosed => "the device queue is closed",
+			Error::InvalidEmulatorAddress(_) => "the configured emulator address is invalid",
 			Error::TransportConnect(_) => "transport error connecting to device",
 			Error::TransportBeginSession(_) => "transport error while beginning a session",
 			Error::TransportEndSession(_) => "transport error while ending a session",
 			Error::TransportSendMessage(_) => "transport error while sending a message",
 			Error::TransportReceiveMessage(_) => "transport error while receiving a message",
+			Error::MessageTooLarge(_) => "the device announced a message that is too large",
 			Error::UnexpectedMessageType(_) => {
 				"received an unexpected message type from the device"
 			}
@@ -115,9 +204,29 @@ impl error::Error for Error {
 			Error::UnexpectedInteractionRequest(_) => {
 				"an unexpected interaction request was returned by the device"
 			}
+			Error::InteractionRequired {
+				..
+			} => "the device requests user interaction",
+			Error::InteractionTimeout(_) => "the user didn't respond on the device in time",
+			Error::DeadlineExceeded => "the deadline of the flow passed",
+			Error::InvalidInteractionToken => "invalid interaction token",
+			Error::InvalidInteractionAnswer(_) => {
+				"the answer doesn't match the pending interaction request"
+			}
+			Error::InvalidAddress(_) => "the given address is invalid",
+			Error::PolicyDenied(_) => "the signing policy denied the request",
 			Error::Base58(ref e) => error::Error::description(e),
+			Error::UnsupportedFeature(_) => "the feature is not supported by the device",
 			Error::UnsupportedNetwork => "given network is not supported",
+			Error::UnsupportedScriptType(_) => "given script type is not supported",
 			Error::InvalidEntropy => "provided entropy is not 32 bytes",
+			Error::InvalidEntropyStrength(_) => "the seed strength is not a valid number of bits",
+			Error::InvalidMnemonic(_) => "the mnemonic is invalid",
+			Error::UnsupportedLanguage(_) => {
+				"the mnemonic language is not supported by the firmware"
+			}
+			Error::EntropyCheckFailed => "the seed entropy does not match the expected entropy",
+			Error::TestVectorMismatch(_) => "the device returned an unexpected test vector value",
 			Error::TxRequestInvalidIndex(_) => {
 				"the device referenced a non-existing input or output index"
 			}
@@ -125,6 +234,8 @@ impl error::Error for Error {
 			Error::PsbtMissingInputTx(_) => "the PSBT is missing the full tx for given input",
 			Error::MalformedTxRequest(_) => "device produced invalid TxRequest message",
 			Error::InvalidPsbt(_) => "user provided invalid PSBT",
+			Error::MalformedEthereumAddress => "device produced an EthereumAddress without address",
+			Error::InvalidStellarTransaction(_) => "user provided invalid Stellar transaction",
 			Error::BitcoinEncode(_) => "error encoding/decoding a Bitcoin data structure",
 			Error::Secp256k1(_) => "elliptic curve crypto error",
 		}
@@ -134,6 +245,16 @@ impl error::Error for Error {
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
+			Error::DeviceChanged {
+				ref expected,
+				ref actual,
+			} => write!(
+				f,
+				"expected device {}, but device {} is connected",
+				expected,
+				actual.as_ref().map(|d| d.as_str()).unwrap_or("without ID")
+			),
+			Error::InvalidEmulatorAddress(ref a) => write!(f, "invalid emulator address: {}", a),
 			Error::TransportConnect(ref e) => write!(f, "transport connect: {}", e),
 			Error::TransportBeginSession(ref e) => write!(f, "transport beginning session: {}", e),
 			Error::TransportEndSession(ref e) => write!(f, "transport ending session: {}", e),
@@ -141,20 +262,40 @@ impl fmt::Display for Error {
 			Error::TransportReceiveMessage(ref e) => {
 				write!(f, "transport receiving message: {}", e)
 			}
+			Error::MessageTooLarge(s) => {
+				write!(f, "the device announced a message that is too large: {} bytes", s)
+			}
 			Error::UnexpectedMessageType(ref t) => {
-				write!(f, "received unexpected message type: {:?}", t)
+				write!(f, "received unexpected message type: {}", t)
 			}
 			Error::Protobuf(ref e) => write!(f, "protobuf: {}", e),
-			Error::FailureResponse(ref e) => write!(
-				f,
-				r#"failure received: code={:?} message="{}""#,
-				e.get_code(),
-				e.get_message()
-			),
+			Error::FailureResponse(ref e) => write!(f, "failure received: {}", e),
 			Error::UnexpectedInteractionRequest(ref r) => {
 				write!(f, "unexpected interaction request: {:?}", r)
 			}
+			Error::InteractionRequired {
+				ref kind,
+				..
+			} => write!(f, "interaction required: {:?}", kind),
+			Error::InteractionTimeout(ref t) => {
+				write!(f, "no user response on the device within {:?}", t)
+			}
+			Error::InvalidInteractionAnswer(ref kind) => {
+				write!(f, "invalid answer for interaction request: {:?}", kind)
+			}
+			Error::InvalidAddress(ref a) => write!(f, "invalid address: {}", a),
+			Error::PolicyDenied(ref r) => write!(f, "denied by signing policy: {}", r),
 			Error::Base58(ref e) => fmt::Display::fmt(e, f),
+			Error::UnsupportedFeature(ref feat) => {
+				write!(f, "feature not supported by the device: {}", feat)
+			}
+			Error::UnsupportedScriptType(ref t) => write!(f, "unsupported script type: {:?}", t),
+			Error::InvalidEntropyStrength(ref s) => write!(f, "invalid seed strength: {}", s),
+			Error::InvalidMnemonic(ref m) => write!(f, "invalid mnemonic: {}", m),
+			Error::TestVectorMismatch(ref m) => write!(f, "unexpected test vector value: {}", m),
+			Error::UnsupportedLanguage(ref l) => {
+				write!(f, "mnemonic language not supported by the firmware: {}", l)
+			}
 			Error::TxRequestInvalidIndex(ref i) => {
 				write!(f, "device referenced non-existing input or output index: {}", i)
 			}
@@ -162,8 +303,11 @@ impl fmt::Display for Error {
 				write!(f, "device referenced unknown TXID: {}", txid)
 			}
 			Error::PsbtMissingInputTx(ref txid) => write!(f, "PSBT missing input tx: {}", txid),
-			Error::MalformedTxRequest(ref m) => write!(f, "malformed TxRequest: {:?}", m),
+			Error::MalformedTxRequest(ref m) => write!(f, "malformed TxRequest: {}", m),
 			Error::InvalidPsbt(ref m) => write!(f, "invalid PSBT: {}", m),
+			Error::InvalidStellarTransaction(ref m) => {
+				write!(f, "invalid Stellar transaction: {}", m)
+			}
 			Error::BitcoinEncode(ref e) => write!(f, "bitcoin encoding error: {}", e),
 			Error::Secp256k1(ref e) => write!(f, "ECDSA signature error: {}", e),
 			_ => f.write_str(error::Error::description(self)),
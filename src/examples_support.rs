@@ -0,0 +1,58 @@
+//! # Examples support
+//!
+//! Shared code for the examples and quick-start programs.  The device to use is picked based on
+//! the `TREZOR_EMULATOR` environment variable, so everything runs against the emulator without
+//! physical hardware:
+//!
+//! - unset or empty: use the single connected hardware device,
+//! - `1`: use the emulator at the default host and port,
+//! - `host` or `host:port`: use the emulator at the given address.
+
+use std::env;
+
+use client::Trezor;
+use error::{Error, Result};
+use {find_emulator_devices, unique, DEFAULT_EMULATOR_HOST, DEFAULT_EMULATOR_PORT};
+
+/// The environment variable to select the emulator.
+pub const EMULATOR_ENV_VAR: &str = "TREZOR_EMULATOR";
+
+/// Get the emulator address configured in the environment, if any.
+pub fn emulator_address() -> Result<Option<(String, u16)>> {
+	let value = match env::var(EMULATOR_ENV_VAR) {
+		Ok(ref v) if v.is_empty() => return Ok(None),
+		Ok(v) => v,
+		Err(_) => return Ok(None),
+	};
+	if value == "1" {
+		return Ok(Some((DEFAULT_EMULATOR_HOST.to_owned(), DEFAULT_EMULATOR_PORT)));
+	}
+
+	let mut parts = value.splitn(2, ':');
+	let host = parts.next().unwrap_or("");
+	let port = match parts.next() {
+		Some(port) => port.parse().map_err(|_| Error::InvalidEmulatorAddress(value.clone()))?,
+		None => DEFAULT_EMULATOR_PORT,
+	};
+	if host.is_empty() {
+		return Err(Error::InvalidEmulatorAddress(value.clone()));
+	}
+	Ok(Some((host.to_owned(), port)))
+}
+
+/// Connect to the emulator or hardware device selected by the environment and initialize it.
+/// The `debug` flag selects the debug link of the device.
+pub fn connect(debug: bool) -> Result<Trezor> {
+	let mut trezor = match emulator_address()? {
+		Some((host, port)) => {
+			let mut devices = find_emulator_devices(&host, port, debug)?;
+			if devices.is_empty() {
+				return Err(Error::NoDeviceFound);
+			}
+			devices.remove(0).connect()?
+		}
+		None => unique(debug)?,
+	};
+	trezor.init_device()?;
+	Ok(trezor)
+}
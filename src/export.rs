@@ -0,0 +1,153 @@
+//! # Wallet export
+//!
+//! Export accounts on the device to watch-only wallet files of other wallet software.
+
+use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
+use bitcoin::util::{base58, bip32};
+use hex;
+
+use error::{Error, Result};
+use types::InputScriptType;
+use utils;
+
+/// The seed version written to Electrum wallet files.  Electrum upgrades older files on load.
+const ELECTRUM_SEED_VERSION: u32 = 17;
+
+/// Get the SLIP-132 extended public key version bytes Electrum uses for the script type.
+fn electrum_xpub_version(script_type: InputScriptType, network: Network) -> Result<[u8; 4]> {
+	let mainnet = network == Network::Bitcoin;
+	match script_type {
+		InputScriptType::SpendAddress if mainnet => Ok([0x04, 0x88, 0xb2, 0x1e]), // xpub
+		InputScriptType::SpendAddress => Ok([0x04, 0x35, 0x87, 0xcf]),            // tpub
+		InputScriptType::SpendP2shWitness if mainnet => Ok([0x04, 0x9d, 0x7c, 0xb2]), // ypub
+		InputScriptType::SpendP2shWitness => Ok([0x04, 0x4a, 0x52, 0x62]),        // upub
+		InputScriptType::SpendWitness if mainnet => Ok([0x04, 0xb2, 0x47, 0x46]), // zpub
+		InputScriptType::SpendWitness => Ok([0x04, 0x5f, 0x1c, 0xf6]),            // vpub
+		t => Err(Error::UnsupportedScriptType(t)),
+	}
+}
+
+/// Encode the extended public key with the version bytes Electrum uses for the script type.
+pub fn electrum_xpub(xpub: &bip32::ExtendedPubKey, script_type: InputScriptType) -> Result<String> {
+	let mut data = base58::from_check(&xpub.to_string())?;
+	data[0..4].copy_from_slice(&electrum_xpub_version(script_type, xpub.network)?);
+	Ok(base58::check_encode_slice(&data))
+}
+
+/// An account to export to an Electrum wallet file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectrumAccount {
+	/// The extended public key of the account.
+	pub xpub: bip32::ExtendedPubKey,
+	/// The derivation path of the account, like m/84'/0'/0'.
+	pub path: Vec<u32>,
+	/// The script type of the account.
+	pub script_type: InputScriptType,
+	/// The fingerprint of the master key.
+	pub root_fingerprint: bip32::Fingerprint,
+	/// The label of the device.
+	pub label: Option<String>,
+	/// The device ID, used by Electrum to find the device.
+	pub device_id: Option<String>,
+}
+
+impl ElectrumAccount {
+	/// Create the contents of an Electrum wallet file for the account.  The wallet is a
+	/// standard wallet with a Trezor hardware keystore, so Electrum can watch the account and
+	/// use the device for signing.
+	pub fn to_wallet_file(&self) -> Result<String> {
+		let mut keystore = Vec::new();
+		keystore.push(format!(
+			"\"derivation\": {}",
+			utils::json_string(&utils::path_to_string(&self.path))
+		));
+		keystore.push("\"hw_type\": \"trezor\"".to_owned());
+		if let Some(ref label) = self.label {
+			keystore.push(format!("\"label\": {}", utils::json_string(label)));
+		}
+		keystore.push(format!(
+			"\"root_fingerprint\": {}",
+			utils::json_string(&hex::encode(&self.root_fingerprint[..]))
+		));
+		if let Some(ref device_id) = self.device_id {
+			keystore.push(format!("\"soft_device_id\": {}", utils::json_string(device_id)));
+		}
+		keystore.push("\"type\": \"hardware\"".to_owned());
+		keystore.push(format!(
+			"\"xpub\": {}",
+			utils::json_string(&electrum_xpub(&self.xpub, self.script_type)?)
+		));
+
+		Ok(format!(
+			"{{\n    \"keystore\": {{\n        {}\n    }},\n    \"seed_version\": {},\n    \
+			 \"use_encryption\": false,\n    \"wallet_type\": \"standard\"\n}}\n",
+			keystore.join(",\n        "),
+			ELECTRUM_SEED_VERSION,
+		))
+	}
+}
+
+/// Get the BIP-44 style account path for the script type, like m/84'/0'/0' for the first native
+/// segwit account on mainnet.
+pub fn account_path(
+	script_type: InputScriptType,
+	network: Network,
+	account: u32,
+) -> Result<Vec<u32>> {
+	let purpose = utils::purpose_from_script_type(script_type)
+		.ok_or(Error::UnsupportedScriptType(script_type))?;
+	let coin_type = match network {
+		Network::Bitcoin => 0,
+		Network::Testnet | Network::Regtest => 1,
+	};
+	Ok(vec![purpose | utils::HARDENED, coin_type | utils::HARDENED, account | utils::HARDENED])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+
+	/// The m/84'/0'/0' account of the BIP-84 test mnemonic, with the standard xpub version.
+	const BIP84_XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+	/// The same account as published in BIP-84.
+	const BIP84_ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+	#[test]
+	fn electrum_xpub_bip84() {
+		let xpub = bip32::ExtendedPubKey::from_str(BIP84_XPUB).unwrap();
+		assert_eq!(electrum_xpub(&xpub, InputScriptType::SpendWitness).unwrap(), BIP84_ZPUB);
+		assert_eq!(electrum_xpub(&xpub, InputScriptType::SpendAddress).unwrap(), BIP84_XPUB);
+		assert!(electrum_xpub(&xpub, InputScriptType::SpendMultisig).is_err());
+	}
+
+	#[test]
+	fn electrum_wallet_file() {
+		let xpub = bip32::ExtendedPubKey::from_str(BIP84_XPUB).unwrap();
+		let fingerprint = xpub.parent_fingerprint;
+		let account = ElectrumAccount {
+			xpub: xpub,
+			path: account_path(InputScriptType::SpendWitness, Network::Bitcoin, 0).unwrap(),
+			script_type: InputScriptType::SpendWitness,
+			root_fingerprint: fingerprint,
+			label: Some("My \"Trezor\"".to_owned()),
+			device_id: Some("6D4CB3F6E7C9F4F1".to_owned()),
+		};
+		let expected = "{
+    \"keystore\": {
+        \"derivation\": \"m/84'/0'/0'\",
+        \"hw_type\": \"trezor\",
+        \"label\": \"My \\\"Trezor\\\"\",
+        \"root_fingerprint\": \"7ef32bdb\",
+        \"soft_device_id\": \"6D4CB3F6E7C9F4F1\",
+        \"type\": \"hardware\",
+        \"xpub\": \"zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs\"
+    },
+    \"seed_version\": 17,
+    \"use_encryption\": false,
+    \"wallet_type\": \"standard\"
+}
+";
+		assert_eq!(account.to_wallet_file().unwrap(), expected);
+	}
+}
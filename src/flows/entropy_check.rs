@@ -0,0 +1,125 @@
+//!
+//! Logic to verify the seed generated by the device in the reset_device command flow.
+//!
+//! When resetting a device with `display_random` set, the device shows its internal entropy on
+//! the display before asking the host for additional entropy.  The seed is then derived as
+//! `SHA-256(internal_entropy || host_entropy)`, truncated to the requested strength.  By keeping
+//! the host entropy around, the host can independently recompute the seed entropy and compare it
+//! with the mnemonic that the device shows during backup, making the initialization auditable.
+//!
+
+use bitcoin_hashes::{sha256, Hash};
+
+use error::{Error, Result};
+
+/// The size in bytes of the internal entropy displayed by the device.
+pub const DEVICE_ENTROPY_SIZE: usize = 32;
+
+/// Calculate the seed entropy the way the device does it from the internal entropy displayed on
+/// the device and the entropy provided by the host.  The strength is given in bits.
+pub fn seed_entropy(
+	device_entropy: &[u8],
+	host_entropy: &[u8],
+	strength: usize,
+) -> Result<Vec<u8>> {
+	if device_entropy.len() != DEVICE_ENTROPY_SIZE {
+		return Err(Error::InvalidEntropy);
+	}
+	if strength % 32 != 0 || strength < 128 || strength > 256 {
+		return Err(Error::InvalidEntropyStrength(strength));
+	}
+
+	let mut data = Vec::with_capacity(device_entropy.len() + host_entropy.len());
+	data.extend_from_slice(device_entropy);
+	data.extend_from_slice(host_entropy);
+	let hash = sha256::Hash::hash(&data).into_inner();
+	Ok(hash[0..strength / 8].to_vec())
+}
+
+/// The host side of the entropy check.  It is returned when the host entropy is provided to the
+/// device and can be used to verify the seed once the device has finished the reset.
+#[derive(Debug, Clone)]
+pub struct EntropyCheck {
+	host_entropy: Vec<u8>,
+	strength: usize,
+}
+
+impl EntropyCheck {
+	pub(crate) fn new(host_entropy: Vec<u8>, strength: usize) -> EntropyCheck {
+		EntropyCheck {
+			host_entropy: host_entropy,
+			strength: strength,
+		}
+	}
+
+	/// The entropy that was provided to the device by the host.
+	pub fn host_entropy(&self) -> &[u8] {
+		&self.host_entropy
+	}
+
+	/// The strength of the seed in bits.
+	pub fn strength(&self) -> usize {
+		self.strength
+	}
+
+	/// The SHA-256 hash of the host entropy, f.e. to record in an audit trail which host entropy
+	/// was used without storing the entropy itself.  This is computed on the host only; it is not
+	/// a commitment made by the device and doesn't verify anything about the device entropy.
+	pub fn host_entropy_hash(&self) -> sha256::Hash {
+		sha256::Hash::hash(&self.host_entropy)
+	}
+
+	/// Calculate the expected seed entropy given the internal entropy displayed by the device.
+	pub fn expected_seed_entropy(&self, device_entropy: &[u8]) -> Result<Vec<u8>> {
+		seed_entropy(device_entropy, &self.host_entropy, self.strength)
+	}
+
+	/// Verify that the seed entropy that ended up on the device matches the one derived from the
+	/// internal entropy displayed by the device and the host entropy.
+	pub fn verify(&self, device_entropy: &[u8], seed_entropy: &[u8]) -> Result<()> {
+		if self.expected_seed_entropy(device_entropy)? == seed_entropy {
+			Ok(())
+		} else {
+			Err(Error::EntropyCheckFailed)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hex;
+
+	/// The host entropy used in the reset tests of the firmware.
+	const HOST_ENTROPY: &[u8] = b"zlutoucky kun upel divoke odyzlutoucky kun upel divoke ody";
+
+	fn device_entropy() -> Vec<u8> {
+		(0..DEVICE_ENTROPY_SIZE as u8).collect()
+	}
+
+	#[test]
+	fn seed_entropy_vector() {
+		let seed = seed_entropy(&device_entropy(), HOST_ENTROPY, 256).unwrap();
+		assert_eq!(
+			hex::encode(&seed),
+			"17b6ef2a55fad4499708364786e19cb943e4cd176e9d7886c2863c24e3c0fb28"
+		);
+		let seed = seed_entropy(&device_entropy(), HOST_ENTROPY, 128).unwrap();
+		assert_eq!(hex::encode(&seed), "17b6ef2a55fad4499708364786e19cb9");
+	}
+
+	#[test]
+	fn verify_seed() {
+		let check = EntropyCheck::new(HOST_ENTROPY.to_vec(), 128);
+		let seed = hex::decode("17b6ef2a55fad4499708364786e19cb9").unwrap();
+		assert!(check.verify(&device_entropy(), &seed).is_ok());
+		match check.verify(&device_entropy(), &seed[1..]) {
+			Err(Error::EntropyCheckFailed) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+		match check.verify(&device_entropy()[1..], &seed) {
+			Err(Error::InvalidEntropy) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+}
@@ -5,35 +5,31 @@
 use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
 use bitcoin::util::psbt;
 use bitcoin::Transaction;
-use bitcoin_hashes::sha256d;
 
 use client::*;
 use error::{Error, Result};
+use interaction::ProgressEvent;
+use policy::SigningRequest;
 use protos;
+use types::{self, TxRequestInfo, TxRequestType};
 use utils;
 
-// Some types with raw protos that we use in the public interface so they have to be exported.
-pub use protos::ButtonRequest_ButtonRequestType as ButtonRequestType;
-pub use protos::Features;
-pub use protos::InputScriptType;
-pub use protos::PinMatrixRequest_PinMatrixRequestType as PinMatrixRequestType;
+use protos::InputScriptType;
 use protos::TxAck_TransactionType_TxOutputType_OutputScriptType as OutputScriptType;
-use protos::TxRequest_RequestType as TxRequestType;
+
+/// Get the requested input or output index or an error if the device didn't provide it.
+fn request_index(info: &TxRequestInfo) -> Result<usize> {
+	info.request_index.ok_or(Error::MalformedTxRequest("missing request index".to_owned()))
+}
 
 /// Fulfill a TxRequest for TXINPUT.
 fn ack_input_request(
-	req: &protos::TxRequest,
+	info: &TxRequestInfo,
 	psbt: &psbt::PartiallySignedTransaction,
 ) -> Result<protos::TxAck> {
-	if !req.has_details() || !req.get_details().has_request_index() {
-		return Err(Error::MalformedTxRequest(req.clone()));
-	}
-
 	// Choose either the tx we are signing or a dependent tx.
-	let input_index = req.get_details().get_request_index() as usize;
-	let input = if req.get_details().has_tx_hash() {
-		let req_hash: sha256d::Hash = utils::from_rev_bytes(req.get_details().get_tx_hash())
-			.ok_or(Error::MalformedTxRequest(req.clone()))?;
+	let input_index = request_index(info)?;
+	let input = if let Some(req_hash) = info.tx_hash {
 		trace!("Preparing ack for input {}:{}", req_hash, input_index);
 		let inp = utils::psbt_find_input(&psbt, req_hash)?;
 		let tx = inp.non_witness_utxo.as_ref().ok_or(Error::PsbtMissingInputTx(req_hash))?;
@@ -52,7 +48,7 @@ fn ack_input_request(
 	data_input.set_sequence(input.sequence);
 
 	// Extra data only for currently signing tx.
-	if !req.get_details().has_tx_hash() {
+	if info.tx_hash.is_none() {
 		let psbt_input = psbt
 			.inputs
 			.get(input_index)
@@ -70,7 +66,7 @@ fn ack_input_request(
 		};
 
 		// If there is exactly 1 HD keypath known, we can provide it.  If more it's multisig.
-		if psbt_input.hd_keypaths.len() == 1 {
+		let path_script_type = if psbt_input.hd_keypaths.len() == 1 {
 			data_input.set_address_n(
 				(psbt_input.hd_keypaths.iter().nth(0).unwrap().1)
 					.1
@@ -79,7 +75,10 @@ fn ack_input_request(
 					.map(|i| i.clone().into())
 					.collect(),
 			);
-		}
+			utils::script_type_from_path(data_input.get_address_n())
+		} else {
+			None
+		};
 
 		// Since we know the keypath, we probably have to sign it.  So update script_type.
 		let script_type = {
@@ -91,6 +90,11 @@ fn ack_input_request(
 				InputScriptType::SPENDWITNESS
 			} else if script_pubkey.is_p2sh() && psbt_input.witness_script.is_some() {
 				InputScriptType::SPENDP2SHWITNESS
+			} else if script_pubkey.is_p2sh()
+				&& path_script_type == Some(types::InputScriptType::SpendP2shWitness)
+			{
+				// A single-key BIP-49 path on a P2SH output is nested segwit.
+				InputScriptType::SPENDP2SHWITNESS
 			} else {
 				//TODO(stevenroose) normal p2sh is probably multisig
 				InputScriptType::EXTERNAL
@@ -112,22 +116,17 @@ fn ack_input_request(
 
 /// Fulfill a TxRequest for TXOUTPUT.
 fn ack_output_request(
-	req: &protos::TxRequest,
+	info: &TxRequestInfo,
 	psbt: &psbt::PartiallySignedTransaction,
-	network: Network,
+	codec: &utils::AddressCodec,
 ) -> Result<protos::TxAck> {
-	if !req.has_details() || !req.get_details().has_request_index() {
-		return Err(Error::MalformedTxRequest(req.clone()));
-	}
+	let output_index = request_index(info)?;
 
 	// For outputs, the Trezor only needs bin_outputs to be set for dependent txs and full outputs
 	// for the signing tx.
 	let mut txdata = protos::TxAck_TransactionType::new();
-	if req.get_details().has_tx_hash() {
+	if let Some(req_hash) = info.tx_hash {
 		// Dependent tx, take the output from the PSBT and just create bin_output.
-		let output_index = req.get_details().get_request_index() as usize;
-		let req_hash: sha256d::Hash = utils::from_rev_bytes(req.get_details().get_tx_hash())
-			.ok_or(Error::MalformedTxRequest(req.clone()))?;
 		trace!("Preparing ack for output {}:{}", req_hash, output_index);
 		let inp = utils::psbt_find_input(&psbt, req_hash)?;
 		let output = if let Some(ref tx) = inp.non_witness_utxo {
@@ -147,7 +146,6 @@ fn ack_output_request(
 		txdata.mut_bin_outputs().push(bin_output);
 	} else {
 		// Signing tx, we need to fill the full output meta object.
-		let output_index = req.get_details().get_request_index() as usize;
 		trace!("Preparing ack for tx output #{}", output_index);
 		let opt = &psbt.global.unsigned_tx.output.get(output_index);
 		let output = opt.ok_or(Error::TxRequestInvalidIndex(output_index))?;
@@ -156,8 +154,8 @@ fn ack_output_request(
 		data_output.set_amount(output.value);
 		// Set script type to PAYTOADDRESS unless we find out otherwise from the PSBT.
 		data_output.set_script_type(OutputScriptType::PAYTOADDRESS);
-		if let Some(addr) = utils::address_from_script(&output.script_pubkey, network) {
-			data_output.set_address(addr.to_string());
+		if let Some(addr) = codec.address_from_script(&output.script_pubkey) {
+			data_output.set_address(addr);
 		}
 
 		let psbt_output = psbt
@@ -201,18 +199,12 @@ fn ack_output_request(
 
 /// Fulfill a TxRequest for TXMETA.
 fn ack_meta_request(
-	req: &protos::TxRequest,
+	info: &TxRequestInfo,
 	psbt: &psbt::PartiallySignedTransaction,
 ) -> Result<protos::TxAck> {
-	if !req.has_details() {
-		return Err(Error::MalformedTxRequest(req.clone()));
-	}
-
 	// Choose either the tx we are signing or a dependent tx.
-	let tx: &Transaction = if req.get_details().has_tx_hash() {
+	let tx: &Transaction = if let Some(req_hash) = info.tx_hash {
 		// dependeny tx, look for it in PSBT inputs
-		let req_hash: sha256d::Hash = utils::from_rev_bytes(req.get_details().get_tx_hash())
-			.ok_or(Error::MalformedTxRequest(req.clone()))?;
 		trace!("Preparing ack for tx meta of {}", req_hash);
 		let inp = utils::psbt_find_input(&psbt, req_hash)?;
 		inp.non_witness_utxo.as_ref().ok_or(Error::PsbtMissingInputTx(req_hash))?
@@ -243,28 +235,41 @@ fn ack_meta_request(
 /// It's important to always first check with the `finished()` method if more data is requested by
 /// the device.  If you're not yet finished you must call the `ack_psbt()` method to send more
 /// information to the device.
+///
+/// If a signing policy checked the transaction, every PSBT provided to the device must be for the
+/// same signing request.
 pub struct SignTxProgress<'a> {
 	client: &'a mut Trezor,
 	req: protos::TxRequest,
+	info: TxRequestInfo,
+	/// The signing request that was checked by the signing policy, if any.
+	checked: Option<SigningRequest>,
 }
 
 impl<'a> SignTxProgress<'a> {
 	/// Only intended for internal usage.
-	pub fn new(client: &mut Trezor, req: protos::TxRequest) -> SignTxProgress {
-		SignTxProgress {
+	pub fn new(
+		client: &mut Trezor,
+		req: protos::TxRequest,
+		checked: Option<SigningRequest>,
+	) -> Result<SignTxProgress> {
+		let info = TxRequestInfo::from_request(&req)?;
+		Ok(SignTxProgress {
 			client: client,
 			req: req,
-		}
+			info: info,
+			checked: checked,
+		})
 	}
 
-	/// Inspector to the request message received from the device.
-	pub fn tx_request(&self) -> &protos::TxRequest {
-		&self.req
+	/// The details of the data the device is requesting.
+	pub fn tx_request(&self) -> &TxRequestInfo {
+		&self.info
 	}
 
 	/// Check whether or not the signing process is finished.
 	pub fn finished(&self) -> bool {
-		self.req.get_request_type() == TxRequestType::TXFINISHED
+		self.info.request_type == TxRequestType::Finished
 	}
 
 	/// Check if a signature is provided by the device.
@@ -304,17 +309,31 @@ impl<'a> SignTxProgress<'a> {
 		}
 	}
 
-	/// Manually provide a TxAck message to the device.
+	/// Manually provide a TxAck message to the device.  Manual acks can't be checked against the
+	/// signing policy, so they are denied with `Error::PolicyDenied` if a policy checked the
+	/// transaction.
 	///
 	/// This method will panic if `finished()` returned true,
 	/// so it should always be checked in advance.
 	pub fn ack_msg(
 		self,
 		ack: protos::TxAck,
+	) -> Result<TrezorResponse<'a, SignTxProgress<'a>, protos::TxRequest>> {
+		if self.checked.is_some() {
+			return Err(Error::PolicyDenied("manual acks can't be checked".to_owned()));
+		}
+		self.send_ack(ack)
+	}
+
+	/// Send the TxAck message to the device.
+	fn send_ack(
+		self,
+		ack: protos::TxAck,
 	) -> Result<TrezorResponse<'a, SignTxProgress<'a>, protos::TxRequest>> {
 		assert!(!self.finished());
 
-		self.client.call(ack, Box::new(|c, m| Ok(SignTxProgress::new(c, m))))
+		let checked = self.checked;
+		self.client.call(ack, Box::new(move |c, m| SignTxProgress::new(c, m, checked.clone())))
 	}
 
 	/// Provide additional PSBT information to the device.
@@ -326,15 +345,147 @@ impl<'a> SignTxProgress<'a> {
 		psbt: &psbt::PartiallySignedTransaction,
 		network: Network,
 	) -> Result<TrezorResponse<'a, SignTxProgress<'a>, protos::TxRequest>> {
-		assert!(self.req.get_request_type() != TxRequestType::TXFINISHED);
-
-		let ack = match self.req.get_request_type() {
-			TxRequestType::TXINPUT => ack_input_request(&self.req, &psbt),
-			TxRequestType::TXOUTPUT => ack_output_request(&self.req, &psbt, network),
-			TxRequestType::TXMETA => ack_meta_request(&self.req, &psbt),
-			TxRequestType::TXEXTRADATA => unimplemented!(), //TODO(stevenroose) implement
-			TxRequestType::TXFINISHED => unreachable!(),
+		self.ack_psbt_with_codec(
+			psbt,
+			&utils::BitcoinCodec {
+				network: network,
+			},
+		)
+	}
+
+	/// Provide additional PSBT information to the device for a coin that encodes its addresses
+	/// with the given codec.  If a signing policy checked the transaction, a PSBT for another
+	/// signing request is denied with `Error::PolicyDenied`.
+	///
+	/// This method will panic if `finished()` returned true,
+	/// so it should always be checked in advance.
+	pub fn ack_psbt_with_codec(
+		self,
+		psbt: &psbt::PartiallySignedTransaction,
+		codec: &utils::AddressCodec,
+	) -> Result<TrezorResponse<'a, SignTxProgress<'a>, protos::TxRequest>> {
+		assert!(!self.finished());
+
+		if let Some(ref checked) = self.checked {
+			if SigningRequest::from_psbt(psbt, codec)? != *checked {
+				return Err(Error::PolicyDenied(
+					"the PSBT differs from the checked transaction".to_owned(),
+				));
+			}
+		}
+
+		// Report the input of the transaction being signed so that it can be highlighted.
+		if self.info.request_type == TxRequestType::Input && self.info.tx_hash.is_none() {
+			if let Some(index) = self.info.request_index {
+				if let Some(input) = psbt.global.unsigned_tx.input.get(index) {
+					self.client.send_progress(ProgressEvent::SigningInput {
+						index: index,
+						previous_output: input.previous_output,
+						amount: utils::psbt_input_amount(psbt, index),
+						path: utils::psbt_input_path(psbt, index),
+					});
+				}
+			}
+		}
+
+		let ack = match self.info.request_type {
+			TxRequestType::Input => ack_input_request(&self.info, &psbt),
+			TxRequestType::Output => ack_output_request(&self.info, &psbt, codec),
+			TxRequestType::Meta => ack_meta_request(&self.info, &psbt),
+			TxRequestType::ExtraData => unimplemented!(), //TODO(stevenroose) implement
+			TxRequestType::Finished => unreachable!(),
 		}?;
-		self.ack_msg(ack)
+		self.send_ack(ack)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bitcoin::{OutPoint, Script, TxIn, TxOut};
+	use policy::{PolicyDecision, SigningPolicy};
+	use protos::TxRequest_RequestType;
+	use testing::{scripted_trezor, FaultPlan, ScriptedLink};
+	use Model;
+
+	struct AllowAll;
+
+	impl SigningPolicy for AllowAll {
+		fn check(&mut self, _request: &SigningRequest) -> PolicyDecision {
+			PolicyDecision::Allow
+		}
+	}
+
+	fn psbt(amount: u64) -> psbt::PartiallySignedTransaction {
+		let tx = Transaction {
+			version: 2,
+			lock_time: 0,
+			input: vec![TxIn {
+				previous_output: OutPoint::default(),
+				script_sig: Script::new(),
+				sequence: 0xffffffff,
+				witness: Vec::new(),
+			}],
+			output: vec![TxOut {
+				value: amount,
+				script_pubkey: Script::new(),
+			}],
+		};
+		psbt::PartiallySignedTransaction::from_unsigned_tx(tx).unwrap()
+	}
+
+	fn tx_request(request_type: TxRequest_RequestType) -> protos::TxRequest {
+		let mut req = protos::TxRequest::new();
+		req.set_request_type(request_type);
+		req.mut_details().set_request_index(0);
+		req
+	}
+
+	/// A client with a signing policy on a device that requests the first output when signing.
+	fn trezor(link: &ScriptedLink) -> Trezor {
+		link.push_message(&protos::Features::new());
+		link.push_message(&tx_request(TxRequest_RequestType::TXOUTPUT));
+		link.push_message(&tx_request(TxRequest_RequestType::TXFINISHED));
+		let mut trezor = scripted_trezor(Model::Trezor2, link.clone(), FaultPlan::new());
+		trezor.init_device().unwrap();
+		trezor.set_signing_policy(Some(Box::new(AllowAll)));
+		trezor
+	}
+
+	#[test]
+	fn ack_checked_psbt() {
+		let link = ScriptedLink::new();
+		let mut trezor = trezor(&link);
+		let progress = trezor.sign_tx(&psbt(1000), Network::Bitcoin).unwrap().ok().unwrap();
+		let progress = progress.ack_psbt(&psbt(1000), Network::Bitcoin).unwrap().ok().unwrap();
+		assert!(progress.finished());
+	}
+
+	#[test]
+	fn ack_other_psbt() {
+		let link = ScriptedLink::new();
+		let mut trezor = trezor(&link);
+		let progress = trezor.sign_tx(&psbt(1000), Network::Bitcoin).unwrap().ok().unwrap();
+		link.take_writes();
+		match progress.ack_psbt(&psbt(2000), Network::Bitcoin) {
+			Err(Error::PolicyDenied(_)) => {}
+			Err(e) => panic!("unexpected error: {:?}", e),
+			Ok(_) => panic!("the other PSBT was sent to the device"),
+		}
+		assert!(link.take_writes().is_empty());
+	}
+
+	#[test]
+	fn manual_ack_with_policy() {
+		let link = ScriptedLink::new();
+		let mut trezor = trezor(&link);
+		let progress = trezor.sign_tx(&psbt(1000), Network::Bitcoin).unwrap().ok().unwrap();
+		link.take_writes();
+		match progress.ack_msg(protos::TxAck::new()) {
+			Err(Error::PolicyDenied(_)) => {}
+			Err(e) => panic!("unexpected error: {:?}", e),
+			Ok(_) => panic!("the manual ack was sent to the device"),
+		}
+		assert!(link.take_writes().is_empty());
 	}
 }
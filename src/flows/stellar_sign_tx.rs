@@ -0,0 +1,109 @@
+//!
+//! Logic to handle the stellar_sign_tx command flow.
+//!
+
+use client::*;
+use error::Result;
+use messages::TrezorMessage;
+use protos;
+use stellar::{Operation, OperationMessage};
+
+/// Send the message of an operation to the device.
+fn send_operation<'a, T, R: TrezorMessage>(
+	client: &'a mut Trezor,
+	msg: OperationMessage,
+	result_handler: Box<ResultHandler<'a, T, R>>,
+) -> Result<TrezorResponse<'a, T, R>> {
+	match msg {
+		OperationMessage::CreateAccount(m) => client.call(m, result_handler),
+		OperationMessage::Payment(m) => client.call(m, result_handler),
+		OperationMessage::PathPayment(m) => client.call(m, result_handler),
+		OperationMessage::ManageOffer(m) => client.call(m, result_handler),
+		OperationMessage::CreatePassiveOffer(m) => client.call(m, result_handler),
+		OperationMessage::SetOptions(m) => client.call(m, result_handler),
+		OperationMessage::ChangeTrust(m) => client.call(m, result_handler),
+		OperationMessage::AllowTrust(m) => client.call(m, result_handler),
+		OperationMessage::AccountMerge(m) => client.call(m, result_handler),
+		OperationMessage::ManageData(m) => client.call(m, result_handler),
+		OperationMessage::BumpSequence(m) => client.call(m, result_handler),
+	}
+}
+
+/// Object to track the progress in the Stellar transaction signing flow.  After the transaction
+/// header, the device asks for the operations one by one.  After the last operation, it returns
+/// the signature.
+///
+/// Use the `ack_operation()` method to send the next operation as long as `last_operation()`
+/// returns false.  The last operation must be sent with the `ack_last_operation()` method, which
+/// returns the public key and the signature.
+pub struct StellarSignTxProgress<'a> {
+	client: &'a mut Trezor,
+	operations: Vec<Operation>,
+	next: usize,
+}
+
+impl<'a> StellarSignTxProgress<'a> {
+	/// Only intended for internal usage.
+	pub fn new(
+		client: &mut Trezor,
+		operations: Vec<Operation>,
+		next: usize,
+	) -> StellarSignTxProgress {
+		StellarSignTxProgress {
+			client: client,
+			operations: operations,
+			next: next,
+		}
+	}
+
+	/// The operation the device is asking for.
+	pub fn next_operation(&self) -> &Operation {
+		&self.operations[self.next]
+	}
+
+	/// The number of operations that still have to be sent, including the next one.
+	pub fn remaining_operations(&self) -> usize {
+		self.operations.len() - self.next
+	}
+
+	/// Check whether the device is asking for the last operation.
+	pub fn last_operation(&self) -> bool {
+		self.remaining_operations() == 1
+	}
+
+	/// Send the next operation to the device.
+	///
+	/// This method will panic if `last_operation()` returned true,
+	/// so it should always be checked in advance.
+	pub fn ack_operation(
+		self,
+	) -> Result<TrezorResponse<'a, StellarSignTxProgress<'a>, protos::StellarTxOpRequest>> {
+		assert!(!self.last_operation());
+
+		let msg = self.next_operation().to_message();
+		let operations = self.operations;
+		let next = self.next + 1;
+		send_operation(
+			self.client,
+			msg,
+			Box::new(move |c, _| Ok(StellarSignTxProgress::new(c, operations.clone(), next))),
+		)
+	}
+
+	/// Send the last operation to the device.  The result is the public key and the signature.
+	///
+	/// This method will panic if `last_operation()` returned false,
+	/// so it should always be checked in advance.
+	pub fn ack_last_operation(
+		self,
+	) -> Result<TrezorResponse<'a, (Vec<u8>, Vec<u8>), protos::StellarSignedTx>> {
+		assert!(self.last_operation());
+
+		let msg = self.next_operation().to_message();
+		send_operation(
+			self.client,
+			msg,
+			Box::new(|_, mut m| Ok((m.take_public_key(), m.take_signature()))),
+		)
+	}
+}
@@ -0,0 +1,41 @@
+//!
+//! Logic to handle the wipe_device command flow.
+//!
+
+use client::*;
+use error::Result;
+use protos;
+use types::DeviceFeatures;
+
+/// The response of the device to a wipe request.
+pub enum WipeDeviceResponse<'a> {
+	/// The device asks the user to confirm the wipe on the device.
+	ConfirmationRequired(WipeDeviceConfirmation<'a>),
+	/// The device was wiped without confirmation.  Contains the new features if the device was
+	/// reinitialized.
+	Wiped(Option<DeviceFeatures>),
+}
+
+/// The "confirm wipe" button request of the device.  Use the `confirm()` method to let the user
+/// confirm the wipe on the device.
+pub struct WipeDeviceConfirmation<'a> {
+	req: ButtonRequest<'a, Option<DeviceFeatures>, protos::Success>,
+}
+
+impl<'a> WipeDeviceConfirmation<'a> {
+	/// Only intended for internal usage.
+	pub fn new(
+		req: ButtonRequest<'a, Option<DeviceFeatures>, protos::Success>,
+	) -> WipeDeviceConfirmation<'a> {
+		WipeDeviceConfirmation {
+			req: req,
+		}
+	}
+
+	/// Ack the button request and wait for the user to confirm the wipe on the device.  Returns
+	/// the new features if the device was reinitialized.  If the user rejects the wipe on the
+	/// device, a failure response is returned as an error.
+	pub fn confirm(self) -> Result<Option<DeviceFeatures>> {
+		self.req.ack()?.ok()
+	}
+}
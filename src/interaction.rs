@@ -0,0 +1,200 @@
+//! # User interaction
+//!
+//! Instead of manually handling every interaction request returned in a `TrezorResponse`, an
+//! implementation of the `InteractionHandler` trait can be used to resolve them.
+
+use std::cmp;
+use std::time::Duration;
+
+use bitcoin::OutPoint;
+
+use error::Result;
+use types::{ButtonRequestType, PinMatrixRequestType};
+
+/// Information about a failed PIN attempt, passed to the interaction handler before retrying.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRetry {
+	/// The number of the retry, starting at 1.
+	pub retry: usize,
+	/// The number of retries left before giving up.
+	pub retries_left: usize,
+	/// The number of invalid PINs entered in this operation.  The device also counts invalid PINs
+	/// entered before, so it can have more failed attempts.
+	pub failed_attempts: u32,
+	/// The delay the device imposes before checking the next PIN after the failed attempts of
+	/// this operation.  The actual delay is longer if the device has more failed attempts.
+	pub backoff: Duration,
+	/// The failure message sent by the device.
+	pub message: String,
+}
+
+/// A handler for the interaction requests the device can send.
+pub trait InteractionHandler {
+	/// The device requests the user to confirm on the device.  Returning an error aborts the
+	/// operation.
+	fn button(&mut self, _request_type: ButtonRequestType) -> Result<()> {
+		Ok(())
+	}
+
+	/// The device requests a PIN.  The PIN must be encoded according to the PIN matrix shown on
+	/// the device.
+	fn pin(&mut self, request_type: PinMatrixRequestType) -> Result<String>;
+
+	/// The device requests a passphrase.  Returning None lets the user enter the passphrase on
+	/// the device.
+	fn passphrase(&mut self, on_device: bool) -> Result<Option<String>>;
+
+	/// The device reported an invalid PIN and the operation is going to be retried.
+	fn pin_invalid(&mut self, _retry: &PinRetry) {}
+}
+
+/// An event sent over the progress channel set with `Trezor::set_progress_sender`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+	/// The device is still waiting for the user to confirm on the device.  Sent periodically
+	/// while waiting.
+	WaitingForUser {
+		elapsed: Duration,
+	},
+	/// The device requests an input of the transaction being signed.  The device walks the
+	/// inputs several times while signing, so the same input can be reported more than once.
+	SigningInput {
+		/// The index of the input.
+		index: usize,
+		/// The output spent by the input.
+		previous_output: OutPoint,
+		/// The amount in satoshis, if the PSBT has the UTXO.
+		amount: Option<u64>,
+		/// The derivation path of the key signing the input, if the PSBT has exactly one.
+		path: Option<Vec<u32>>,
+	},
+	/// The device delays checking the next PIN after invalid PINs.  Sent before retrying, so the
+	/// UI can show a countdown while the device doesn't respond.  The wait is based on the invalid
+	/// PINs entered in the operation, so the device can wait longer.
+	DeviceBackoff {
+		wait: Duration,
+	},
+}
+
+/// A token identifying an interaction request that was returned in an
+/// `Error::InteractionRequired` error.  It is used to resume the operation with
+/// `Trezor::resume`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InteractionToken(pub(crate) u64);
+
+/// The answer to an interaction request, used to resume an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InteractionAnswer {
+	/// Confirm that the user was asked to press the button on the device.
+	Button,
+	/// The PIN, encoded according to the PIN matrix shown on the device.
+	Pin(String),
+	/// The passphrase, or None to let the user enter it on the device.
+	Passphrase(Option<String>),
+}
+
+/// Get the delay the device imposes before checking a PIN after the given number of failed
+/// attempts.  The delay doubles with every failed attempt: 2^fails - 1 seconds.
+pub fn pin_backoff(failed_attempts: u32) -> Duration {
+	let fails = cmp::min(failed_attempts, 32);
+	Duration::from_secs((1u64 << fails) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::mpsc;
+
+	use error::Error;
+	use protos;
+	use testing::{scripted_trezor, FaultPlan, ScriptedLink};
+	use types::FailureCode;
+	use Model;
+
+	/// A handler that always enters the same PIN and records the retries.
+	#[derive(Default)]
+	struct PinHandler {
+		retries: Vec<PinRetry>,
+	}
+
+	impl InteractionHandler for PinHandler {
+		fn pin(&mut self, _request_type: PinMatrixRequestType) -> Result<String> {
+			Ok("1234".to_owned())
+		}
+
+		fn passphrase(&mut self, _on_device: bool) -> Result<Option<String>> {
+			Ok(None)
+		}
+
+		fn pin_invalid(&mut self, retry: &PinRetry) {
+			self.retries.push(retry.clone());
+		}
+	}
+
+	fn pin_invalid(message: &str) -> protos::Failure {
+		let mut failure = protos::Failure::new();
+		failure.set_code(protos::Failure_FailureType::Failure_PinInvalid);
+		failure.set_message(message.to_owned());
+		failure
+	}
+
+	#[test]
+	fn backoff() {
+		assert_eq!(pin_backoff(0), Duration::from_secs(0));
+		assert_eq!(pin_backoff(1), Duration::from_secs(1));
+		assert_eq!(pin_backoff(2), Duration::from_secs(3));
+		assert_eq!(pin_backoff(5), Duration::from_secs(31));
+		assert_eq!(pin_backoff(32), Duration::from_secs(u32::max_value() as u64));
+		assert_eq!(pin_backoff(100), pin_backoff(32));
+	}
+
+	#[test]
+	fn retry_invalid_pins() {
+		let link = ScriptedLink::new();
+		// The failure messages of the original and the newer firmware.
+		for message in &["Invalid PIN", "PIN invalid"] {
+			link.push_message(&protos::PinMatrixRequest::new());
+			link.push_message(&pin_invalid(message));
+		}
+		link.push_message(&protos::PinMatrixRequest::new());
+		link.push_message(&protos::Success::new());
+		let mut trezor = scripted_trezor(Model::Trezor2, link, FaultPlan::new());
+		let (sender, receiver) = mpsc::channel();
+		trezor.set_progress_sender(Some(sender));
+
+		let mut handler = PinHandler::default();
+		trezor.with_pin_retries(2, &mut handler, |t| t.ping("")).unwrap();
+		let failed: Vec<u32> = handler.retries.iter().map(|r| r.failed_attempts).collect();
+		assert_eq!(failed, vec![1, 2]);
+		assert_eq!(handler.retries[1].backoff, Duration::from_secs(3));
+		assert_eq!(handler.retries[1].message, "PIN invalid");
+		let waits: Vec<Duration> = receiver
+			.try_iter()
+			.filter_map(|e| match e {
+				ProgressEvent::DeviceBackoff {
+					wait,
+				} => Some(wait),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(waits, vec![Duration::from_secs(1), Duration::from_secs(3)]);
+	}
+
+	#[test]
+	fn no_retries_left() {
+		let link = ScriptedLink::new();
+		link.push_message(&protos::PinMatrixRequest::new());
+		link.push_message(&pin_invalid("Invalid PIN"));
+		let mut trezor = scripted_trezor(Model::Trezor2, link, FaultPlan::new());
+
+		let mut handler = PinHandler::default();
+		match trezor.with_pin_retries(0, &mut handler, |t| t.ping("")) {
+			Err(Error::FailureResponse(ref f)) => {
+				assert_eq!(f.code(), Some(FailureCode::PinInvalid));
+				assert_eq!(f.message(), "Invalid PIN");
+			}
+			r => panic!("unexpected result: {:?}", r),
+		}
+		assert!(handler.retries.is_empty());
+	}
+}
@@ -4,7 +4,16 @@
 //!
 //! Use the public top-level methods `find_devices()` and `unique()` to find devices.  When using
 //! `find_devices()`, a list of different available devices is returned.  To connect to one or more
-//! of them, use their `connect()` method.
+//! of them, use their `connect()` method.  Before using the device, a session has to be started
+//! with `init_device()`; other calls return `Error::NotInitialized` until then.
+//!
+//! ## Stability
+//!
+//! The types used in the public interface are defined in this crate, including the errors.  The
+//! raw protobuf messages are available in the `protos` module for users that want to extend this
+//! library, but they are not covered by semver guarantees.  This includes the second type
+//! parameter of `TrezorResponse` and the interaction request types, which is the raw message that
+//! is expected from the device; don't name it in your own code.
 //!
 //! ## Logging
 //!
@@ -12,10 +21,16 @@
 //! Please be aware that `trace` logging can contain sensitive data.
 //!
 
+#[cfg(feature = "groestlcoin")]
+extern crate bech32;
 extern crate bitcoin;
 extern crate bitcoin_bech32;
 extern crate bitcoin_hashes;
+#[cfg(feature = "decred")]
+extern crate blake_hash;
 extern crate byteorder;
+#[cfg(feature = "groestlcoin")]
+extern crate groestl;
 extern crate hex;
 extern crate hid;
 extern crate libusb;
@@ -24,28 +39,62 @@ extern crate unicode_normalization;
 extern crate log;
 extern crate protobuf;
 extern crate secp256k1;
+/// The raw protobuf messages.
+pub extern crate trezor_protos as protos;
 
+mod cache;
 mod messages;
 mod transport;
 
+pub mod audit;
 pub mod client;
 pub mod error;
-pub mod protos;
+pub mod examples_support;
+pub mod export;
+pub mod interaction;
+pub mod logging;
+pub mod policy;
+pub mod psbt_diff;
+pub mod queue;
+pub mod stellar;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod types;
 pub mod utils;
+pub mod verify;
+pub mod wordlist;
 
 mod flows {
+	pub mod entropy_check;
 	pub mod sign_tx;
+	pub mod stellar_sign_tx;
+	pub mod wipe_device;
 }
 
 pub use client::{
-	ButtonRequest, ButtonRequestType, EntropyRequest, Features, InputScriptType, InteractionType,
-	PassphraseRequest, PinMatrixRequest, PinMatrixRequestType, Trezor, TrezorResponse, WordCount,
+	ButtonRequest, EntropyRequest, InteractionType, PassphraseRequest, PinMatrixRequest, Trezor,
+	TrezorResponse, WordCount,
 };
 pub use error::{Error, Result};
+pub use flows::entropy_check::EntropyCheck;
 pub use flows::sign_tx::SignTxProgress;
+pub use flows::stellar_sign_tx::StellarSignTxProgress;
+pub use flows::wipe_device::{WipeDeviceConfirmation, WipeDeviceResponse};
+pub use interaction::{
+	InteractionAnswer, InteractionHandler, InteractionToken, PinRetry, ProgressEvent,
+};
 pub use messages::TrezorMessage;
+pub use transport::hid::HidVersion;
+pub use types::{
+	AddressOptions, AddressType, BootloaderInfo, ButtonRequestType, Capability, Chain,
+	DeviceFeatures, Failure, FailureCode, InputScriptType, MessageKind, MnemonicOptions,
+	PassphraseMode, PinMatrixRequestType, TxRequestInfo, TxRequestType,
+};
 
 use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// The different kind of Trezor device models.
 #[derive(PartialEq, Eq, Clone, Debug, Copy)]
@@ -66,8 +115,9 @@ impl fmt::Display for Model {
 }
 
 /// A device found by the `find_devices()` method.  It can be connected to using the `connect()`
-/// method.
-#[derive(Debug)]
+/// method.  Devices can be stored and cloned to connect to them again later, or identified by
+/// their transport kind and ID to find them again with `connect_by_id()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AvailableDevice {
 	pub model: Model,
 	pub debug: bool,
@@ -80,10 +130,77 @@ impl fmt::Display for AvailableDevice {
 	}
 }
 
+/// Options for connecting to a device.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+	/// Force the HID version instead of probing it, f.e. for troubleshooting.  Only used for HID
+	/// devices.
+	pub hid_version: Option<HidVersion>,
+}
+
+/// The version of the JSON format of `AvailableDevice::to_json()` and `find_devices_json()`.  It
+/// is increased when fields are changed or removed, but not when fields are added.
+pub const DEVICES_JSON_VERSION: u32 = 1;
+
 impl AvailableDevice {
+	/// Encode the device as a JSON object, f.e. for passing it to another process.  The object
+	/// has the fields `model` (`trezor1`, `trezor2` or `trezor2_bootloader`), `transport` (`hid`,
+	/// `webusb` or `udp`), `id` (the identifier of the device on the transport) and `debug`.
+	pub fn to_json(&self) -> String {
+		let model = match self.model {
+			Model::Trezor1 => "trezor1",
+			Model::Trezor2 => "trezor2",
+			Model::Trezor2Bl => "trezor2_bootloader",
+		};
+		format!(
+			"{{\"model\":{},\"transport\":{},\"id\":{},\"debug\":{}}}",
+			utils::json_string(model),
+			utils::json_string(self.transport.kind()),
+			utils::json_string(&self.transport.id()),
+			self.debug
+		)
+	}
+
+	/// A short name of the kind of transport of the device: `hid`, `webusb` or `udp`.
+	pub fn transport_kind(&self) -> &'static str {
+		self.transport.kind()
+	}
+
+	/// The identifier of the device on the transport: the serial number for HID, the bus and
+	/// address for WebUSB and the host and port for UDP.  The WebUSB address can change when the
+	/// device is reconnected.
+	pub fn transport_id(&self) -> String {
+		self.transport.id()
+	}
+
 	/// Connect to the device.
 	pub fn connect(self) -> Result<Trezor> {
-		let transport = transport::connect(&self).map_err(|e| Error::TransportConnect(e))?;
+		self.connect_with_options(ConnectOptions::default())
+	}
+
+	/// Connect to the device using the given options.
+	pub fn connect_with_options(self, options: ConnectOptions) -> Result<Trezor> {
+		self.connect_transport(options, transport::Faults::default())
+	}
+
+	/// Connect to the device using the given options and inject the faults of the plan in the
+	/// communication with it, for resilience testing.
+	#[cfg(any(test, feature = "testing"))]
+	pub fn connect_with_faults(
+		self,
+		options: ConnectOptions,
+		faults: testing::FaultPlan,
+	) -> Result<Trezor> {
+		self.connect_transport(options, faults)
+	}
+
+	fn connect_transport(
+		self,
+		options: ConnectOptions,
+		faults: transport::Faults,
+	) -> Result<Trezor> {
+		let transport =
+			transport::connect(&self, &options, faults).map_err(|e| Error::TransportConnect(e))?;
 		Ok(client::trezor_with_transport(self.model, transport))
 	}
 }
@@ -107,6 +224,127 @@ pub fn find_hid_devices() -> Result<Vec<AvailableDevice>> {
 	Ok(HidTransport::find_devices(true).map_err(|e| Error::TransportConnect(e))?)
 }
 
+/// The host the emulator listens on by default.
+pub const DEFAULT_EMULATOR_HOST: &str = "127.0.0.1";
+
+/// The port the emulator listens on by default.  The debug link listens on the next port.
+pub const DEFAULT_EMULATOR_PORT: u16 = 21324;
+
+/// Search for an emulator listening on UDP at the given host and port.  With `debug`, the debug
+/// link on the next port is used.  The emulator doesn't report its model before it's
+/// initialized, so it's assumed to be the Trezor 2 emulator.
+pub fn find_emulator_devices(host: &str, port: u16, debug: bool) -> Result<Vec<AvailableDevice>> {
+	use transport::udp::UdpTransport;
+	Ok(UdpTransport::find_devices(host, port, debug).map_err(|e| Error::TransportConnect(e))?)
+}
+
+/// The default time to wait for a single transport to enumerate its devices.
+pub const DEFAULT_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Search for all available devices on the WebUSB and HID transports and for an emulator at the
+/// default host and port.  The transports are probed in parallel so that a hanging transport
+/// doesn't block discovery on the others.  Devices behind the Trezor Bridge are not found,
+/// because this crate doesn't support the bridge.
+pub fn find_all_devices(debug: bool) -> Vec<AvailableDevice> {
+	find_all_devices_with_timeout(debug, DEFAULT_DISCOVERY_TIMEOUT)
+}
+
+/// Search for all available devices on the WebUSB and HID transports and for an emulator at the
+/// default host and port, waiting at most the given timeout for each transport.  Transports that
+/// fail or time out are skipped.
+pub fn find_all_devices_with_timeout(debug: bool, timeout: Duration) -> Vec<AvailableDevice> {
+	use transport::hid::HidTransport;
+	use transport::udp::UdpTransport;
+	use transport::webusb::WebUsbTransport;
+
+	type Probe = fn(bool) -> ::std::result::Result<Vec<AvailableDevice>, transport::error::Error>;
+	fn find_default_emulator(
+		debug: bool,
+	) -> ::std::result::Result<Vec<AvailableDevice>, transport::error::Error> {
+		UdpTransport::find_devices(DEFAULT_EMULATOR_HOST, DEFAULT_EMULATOR_PORT, debug)
+	}
+	let probes = vec![
+		("WebUSB", WebUsbTransport::find_devices as Probe),
+		("HID", HidTransport::find_devices as Probe),
+		("UDP", find_default_emulator as Probe),
+	];
+
+	let (tx, rx) = mpsc::channel();
+	for (name, probe) in probes.iter().cloned() {
+		let tx = tx.clone();
+		thread::spawn(move || {
+			let devices = match probe(debug) {
+				Ok(devices) => devices,
+				Err(e) => {
+					warn!("Error finding {} devices: {}", name, e);
+					Vec::new()
+				}
+			};
+			// The receiver might have given up on us already.
+			let _ = tx.send((name, devices));
+		});
+	}
+
+	let deadline = Instant::now() + timeout;
+	let mut devices = Vec::new();
+	let mut pending: Vec<&'static str> = probes.iter().map(|p| p.0).collect();
+	while !pending.is_empty() {
+		let now = Instant::now();
+		if now >= deadline {
+			break;
+		}
+		match rx.recv_timeout(deadline - now) {
+			Ok((name, found)) => {
+				trace!("Found {} {} devices", found.len(), name);
+				pending.retain(|p| *p != name);
+				devices.extend(found);
+			}
+			Err(_) => break,
+		}
+	}
+	for name in pending {
+		warn!("Timeout finding {} devices", name);
+	}
+	devices
+}
+
+/// Search for all available devices like `find_all_devices()` and encode them as JSON.  The
+/// result is an object with the fields `version`, see `DEVICES_JSON_VERSION`, and `devices`, a
+/// list of the devices as encoded by `AvailableDevice::to_json()`.
+pub fn find_devices_json(debug: bool) -> String {
+	let devices: Vec<String> = find_all_devices(debug).iter().map(|d| d.to_json()).collect();
+	format!("{{\"version\":{},\"devices\":[{}]}}", DEVICES_JSON_VERSION, devices.join(","))
+}
+
+/// Find the device with the given transport kind and ID, as returned by
+/// `AvailableDevice::transport_kind()` and `AvailableDevice::transport_id()`, and connect to it.
+/// For the `udp` transport, the ID is the host and port of the emulator.
+pub fn connect_by_id(transport_kind: &str, id: &str, debug: bool) -> Result<Trezor> {
+	let devices = if transport_kind == "udp" {
+		let mut parts = id.rsplitn(2, ':');
+		let port: Option<u16> = parts.next().and_then(|p| p.parse().ok());
+		// The ID of the debug link has the debug port, which is the next port.
+		let port = if debug {
+			port.and_then(|p| p.checked_sub(1))
+		} else {
+			port
+		};
+		match (parts.next(), port) {
+			(Some(host), Some(port)) => find_emulator_devices(host, port, debug)?,
+			_ => return Err(Error::NoDeviceFound),
+		}
+	} else {
+		find_all_devices(debug)
+	};
+	devices
+		.into_iter()
+		.find(|d| {
+			d.debug == debug && d.transport.kind() == transport_kind && d.transport.id() == id
+		})
+		.ok_or(Error::NoDeviceFound)?
+		.connect()
+}
+
 /// Try to get a single device.  Optionally specify whether debug should be enabled or not.
 /// Can error if there are multiple or no devices available.
 /// For more fine-grained device selection, use `find_devices()`.
@@ -123,3 +361,39 @@ pub fn unique(debug: bool) -> Result<Trezor> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use transport::hid::AvailableHidTransport;
+	use transport::webusb::AvailableWebUsbTransport;
+	use transport::AvailableDeviceTransport;
+
+	#[test]
+	fn device_json() {
+		let webusb = AvailableDevice {
+			model: Model::Trezor2,
+			debug: false,
+			transport: AvailableDeviceTransport::WebUsb(AvailableWebUsbTransport {
+				bus: 1,
+				address: 12,
+			}),
+		};
+		assert_eq!(
+			webusb.to_json(),
+			r#"{"model":"trezor2","transport":"webusb","id":"1:12","debug":false}"#
+		);
+
+		let hid = AvailableDevice {
+			model: Model::Trezor1,
+			debug: true,
+			transport: AvailableDeviceTransport::Hid(AvailableHidTransport {
+				serial_nb: "A1\"B".to_owned(),
+			}),
+		};
+		assert_eq!(
+			hid.to_json(),
+			r#"{"model":"trezor1","transport":"hid","id":"A1\"B","debug":true}"#
+		);
+	}
+}
@@ -0,0 +1,164 @@
+//! # Payload logging
+//!
+//! The messages sent to and received from the device are logged at the trace level.  Some of
+//! them are large, like firmware images and big transactions, so messages above a configurable
+//! size are logged as a summary with the length and the hex of the first and last bytes.
+//! Optionally, the full payloads of these messages are dumped to files instead.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hex;
+use protobuf::Message;
+
+use protos::MessageType;
+
+/// The default maximum size of payloads that are logged in full.
+pub const DEFAULT_MAX_LOGGED_PAYLOAD: usize = 1024;
+
+/// The sequence number of the next dumped payload, to keep the file names unique.
+static DUMP_SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// The configuration of the logging of message payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadLogConfig {
+	/// Payloads up to this size in bytes are logged in full.  Larger payloads are truncated to
+	/// this number of bytes, half from the start and half from the end.  For an odd size, the
+	/// extra byte is taken from the start.
+	pub max_len: usize,
+	/// Dump the full payloads that are too large to log to files in this directory.  Payloads
+	/// are only dumped when their message is logged, so the trace level has to be enabled for the
+	/// `trezor::client` target.
+	pub dump_dir: Option<PathBuf>,
+}
+
+impl Default for PayloadLogConfig {
+	fn default() -> PayloadLogConfig {
+		PayloadLogConfig {
+			max_len: DEFAULT_MAX_LOGGED_PAYLOAD,
+			dump_dir: None,
+		}
+	}
+}
+
+impl PayloadLogConfig {
+	/// Describe the message for the log: the message itself if it's small enough, otherwise a
+	/// summary of its payload.
+	pub(crate) fn describe_message<M: Message>(
+		&self,
+		message_type: MessageType,
+		msg: &M,
+	) -> String {
+		if msg.compute_size() as usize <= self.max_len {
+			return format!("{:?}", msg);
+		}
+		match msg.write_to_bytes() {
+			Ok(payload) => self.describe_payload(message_type, &payload),
+			Err(e) => format!("<unencodable message: {}>", e),
+		}
+	}
+
+	/// Describe the raw payload for the log: the hex of the payload if it's small enough,
+	/// otherwise a summary or the path it was dumped to.
+	pub(crate) fn describe_payload(&self, message_type: MessageType, payload: &[u8]) -> String {
+		if payload.len() <= self.max_len {
+			return hex::encode(payload);
+		}
+		if let Some(ref dir) = self.dump_dir {
+			match dump_payload(dir, message_type, payload) {
+				Ok(path) => {
+					return format!("<{} bytes, dumped to {}>", payload.len(), path.display())
+				}
+				Err(e) => warn!("Failed to dump {:?} payload: {}", message_type, e),
+			}
+		}
+		let head = self.max_len - self.max_len / 2;
+		let tail = payload.len() - self.max_len / 2;
+		format!(
+			"<{} bytes, [0..{}]: {} ... [{}..{}]: {}>",
+			payload.len(),
+			head,
+			hex::encode(&payload[..head]),
+			tail,
+			payload.len(),
+			hex::encode(&payload[tail..])
+		)
+	}
+}
+
+/// Write the payload to a new file in the directory and return its path.
+fn dump_payload(
+	dir: &PathBuf,
+	message_type: MessageType,
+	payload: &[u8],
+) -> ::std::io::Result<PathBuf> {
+	let millis = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs() * 1000 + d.subsec_millis() as u64)
+		.unwrap_or(0);
+	let seq = DUMP_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+	let path = dir.join(format!("{}-{}-{:?}.bin", millis, seq, message_type));
+	fs::write(&path, payload)?;
+	Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::process;
+
+	use protos::MessageType::MessageType_Ping;
+
+	fn config(max_len: usize) -> PayloadLogConfig {
+		PayloadLogConfig {
+			max_len: max_len,
+			dump_dir: None,
+		}
+	}
+
+	fn payload() -> Vec<u8> {
+		(0..10).collect()
+	}
+
+	#[test]
+	fn small_payload() {
+		assert_eq!(
+			config(10).describe_payload(MessageType_Ping, &payload()),
+			"00010203040506070809"
+		);
+	}
+
+	#[test]
+	fn truncated_payload() {
+		assert_eq!(
+			config(4).describe_payload(MessageType_Ping, &payload()),
+			"<10 bytes, [0..2]: 0001 ... [8..10]: 0809>"
+		);
+		assert_eq!(
+			config(5).describe_payload(MessageType_Ping, &payload()),
+			"<10 bytes, [0..3]: 000102 ... [8..10]: 0809>"
+		);
+	}
+
+	#[test]
+	fn dumped_payload() {
+		let dir = env::temp_dir().join(format!("trezor-payload-log-{}", process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let config = PayloadLogConfig {
+			max_len: 4,
+			dump_dir: Some(dir.clone()),
+		};
+
+		let description = config.describe_payload(MessageType_Ping, &payload());
+		let prefix = "<10 bytes, dumped to ";
+		assert!(description.starts_with(prefix) && description.ends_with('>'), "{}", description);
+		let path = PathBuf::from(&description[prefix.len()..description.len() - 1]);
+		assert_eq!(path.parent(), Some(dir.as_path()));
+		assert!(path.to_str().unwrap().ends_with("-MessageType_Ping.bin"));
+		assert_eq!(fs::read(&path).unwrap(), payload());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -9,6 +9,13 @@ use protos::*;
 /// type code.  This getter is implemented in this file for all the messages we use.
 pub trait TrezorMessage: protobuf::Message {
 	fn message_type() -> MessageType;
+
+	/// Whether the required fields of the message are enforced when it's received from the
+	/// device.  This is disabled for messages for which newer firmware no longer sends fields
+	/// that are marked as required in our protos.
+	fn enforce_required_fields() -> bool {
+		true
+	}
 }
 
 /// This macro provides the TrezorMessage trait for a protobuf message.
@@ -87,7 +94,16 @@ trezor_message_impl!(DebugLinkMemory, MessageType_DebugLinkMemory);
 trezor_message_impl!(DebugLinkMemoryWrite, MessageType_DebugLinkMemoryWrite);
 trezor_message_impl!(DebugLinkFlashErase, MessageType_DebugLinkFlashErase);
 trezor_message_impl!(EthereumGetAddress, MessageType_EthereumGetAddress);
-trezor_message_impl!(EthereumAddress, MessageType_EthereumAddress);
+// Newer firmware sends the address as a string in a new field instead of the required bytes.
+impl TrezorMessage for EthereumAddress {
+	fn message_type() -> MessageType {
+		MessageType_EthereumAddress
+	}
+
+	fn enforce_required_fields() -> bool {
+		false
+	}
+}
 trezor_message_impl!(EthereumSignTx, MessageType_EthereumSignTx);
 trezor_message_impl!(EthereumTxRequest, MessageType_EthereumTxRequest);
 trezor_message_impl!(EthereumTxAck, MessageType_EthereumTxAck);
@@ -0,0 +1,119 @@
+//! # Signing policy
+//!
+//! An optional policy that is consulted before every signing request.  The policy is provided by
+//! the user of the library and gets the full parsed request, so it can enforce allowlists or
+//! spending limits for all signing flows in one place.  A denied request is aborted before
+//! anything is sent to the device.
+
+use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
+use bitcoin::util::psbt;
+use bitcoin::{OutPoint, Script};
+
+use error::Result;
+use stellar;
+use types::InputScriptType;
+use utils;
+
+/// A transaction input as presented to the device for signing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyInput {
+	/// The output spent by the input.
+	pub previous_output: OutPoint,
+	/// The amount in satoshis, if the PSBT has the UTXO.
+	pub amount: Option<u64>,
+	/// The derivation path of the key signing the input, if the PSBT has exactly one.
+	pub path: Option<Vec<u32>>,
+}
+
+/// A transaction output as presented to the device for signing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyOutput {
+	/// The destination address, if the script has one.
+	pub address: Option<String>,
+	/// The output script.
+	pub script_pubkey: Script,
+	/// The amount in satoshis.
+	pub amount: u64,
+	/// The derivation path if the output is change.
+	pub change_path: Option<Vec<u32>>,
+}
+
+/// A signing request to be checked by the policy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SigningRequest {
+	/// A transaction of Bitcoin or another Bitcoin-family coin.
+	Transaction {
+		coin_name: String,
+		inputs: Vec<PolicyInput>,
+		outputs: Vec<PolicyOutput>,
+		lock_time: u32,
+	},
+	/// A message signed with a Bitcoin key.
+	Message {
+		path: Vec<u32>,
+		script_type: InputScriptType,
+		network: Network,
+		message: Vec<u8>,
+	},
+	/// A Stellar transaction.
+	StellarTransaction {
+		path: Vec<u32>,
+		tx: stellar::Transaction,
+	},
+}
+
+impl SigningRequest {
+	/// Create the request for signing the PSBT.
+	pub(crate) fn from_psbt(
+		psbt: &psbt::PartiallySignedTransaction,
+		codec: &utils::AddressCodec,
+	) -> Result<SigningRequest> {
+		let tx = &psbt.global.unsigned_tx;
+		let inputs = tx
+			.input
+			.iter()
+			.enumerate()
+			.map(|(idx, i)| PolicyInput {
+				previous_output: i.previous_output,
+				amount: utils::psbt_input_amount(psbt, idx),
+				path: utils::psbt_input_path(psbt, idx),
+			})
+			.collect();
+		let outputs = tx
+			.output
+			.iter()
+			.enumerate()
+			.map(|(idx, o)| PolicyOutput {
+				address: codec.address_from_script(&o.script_pubkey),
+				script_pubkey: o.script_pubkey.clone(),
+				amount: o.value,
+				change_path: psbt
+					.outputs
+					.get(idx)
+					.filter(|o| o.hd_keypaths.len() == 1)
+					.and_then(|o| o.hd_keypaths.values().next())
+					.map(|v| utils::convert_path(&v.1)),
+			})
+			.collect();
+		Ok(SigningRequest::Transaction {
+			coin_name: codec.coin_name()?,
+			inputs: inputs,
+			outputs: outputs,
+			lock_time: tx.lock_time,
+		})
+	}
+}
+
+/// The decision of the policy on a signing request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+	/// The request may be sent to the device.
+	Allow,
+	/// The request is denied for the given reason.
+	Deny(String),
+}
+
+/// A policy that decides whether signing requests may be sent to the device.
+pub trait SigningPolicy {
+	fn check(&mut self, request: &SigningRequest) -> PolicyDecision;
+}
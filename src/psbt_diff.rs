@@ -0,0 +1,324 @@
+//! # PSBT diff
+//!
+//! Compare a PSBT before and after signing to report which inputs gained signatures and which
+//! inputs still need them.  This is useful in multisig coordination, where a PSBT is passed
+//! between signers and partially-signed states are normal.
+
+use std::fmt;
+
+use bitcoin::util::psbt;
+use bitcoin::{PublicKey, Script};
+
+use error::{Error, Result};
+
+/// The number of the OP_1 opcode, OP_2 up to OP_16 follow it.
+const OP_1: u8 = 0x51;
+/// The number of the OP_16 opcode.
+const OP_16: u8 = 0x60;
+/// The number of the OP_CHECKMULTISIG opcode.
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// The signing status of a PSBT input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputStatus {
+	/// The input already has a final scriptSig or witness.
+	Finalized,
+	/// The input has enough signatures to be finalized.
+	Finalizable,
+	/// The input needs the given number of additional signatures.
+	MissingSignatures(usize),
+	/// The number of required signatures can't be determined, f.e. because the PSBT lacks the
+	/// UTXO or the script is not a standard single-key or multisig script.
+	Unknown,
+}
+
+/// The changes to a single PSBT input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputDiff {
+	/// The index of the input.
+	pub index: usize,
+	/// The keys of the signatures that were added.
+	pub new_signatures: Vec<PublicKey>,
+	/// The total number of signatures on the input after signing.
+	pub signatures: usize,
+	/// The status of the input after signing.
+	pub status: InputStatus,
+}
+
+/// The changes between a PSBT before and after signing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtDiff {
+	/// The changes for every input, in the order of the inputs.
+	pub inputs: Vec<InputDiff>,
+}
+
+impl PsbtDiff {
+	/// Compare the PSBT before and after signing.  Both PSBTs must have the same unsigned
+	/// transaction.
+	pub fn between(
+		before: &psbt::PartiallySignedTransaction,
+		after: &psbt::PartiallySignedTransaction,
+	) -> Result<PsbtDiff> {
+		if before.global.unsigned_tx != after.global.unsigned_tx
+			|| before.inputs.len() != after.inputs.len()
+		{
+			return Err(Error::InvalidPsbt("the PSBTs have different transactions".to_owned()));
+		}
+
+		let inputs = before
+			.inputs
+			.iter()
+			.zip(after.inputs.iter())
+			.enumerate()
+			.map(|(idx, (old, new))| {
+				let mut new_signatures: Vec<PublicKey> = new
+					.partial_sigs
+					.keys()
+					.filter(|pk| !old.partial_sigs.contains_key(pk))
+					.cloned()
+					.collect();
+				new_signatures.sort_by_key(|pk| pk.to_bytes());
+				InputDiff {
+					index: idx,
+					new_signatures: new_signatures,
+					signatures: new.partial_sigs.len(),
+					status: input_status(after, idx),
+				}
+			})
+			.collect();
+		Ok(PsbtDiff {
+			inputs: inputs,
+		})
+	}
+
+	/// The indices of the inputs that gained signatures.
+	pub fn signed_inputs(&self) -> Vec<usize> {
+		self.inputs.iter().filter(|i| !i.new_signatures.is_empty()).map(|i| i.index).collect()
+	}
+
+	/// The indices of the inputs that still need signatures or whose status is unknown.
+	pub fn incomplete_inputs(&self) -> Vec<usize> {
+		self.inputs
+			.iter()
+			.filter(|i| match i.status {
+				InputStatus::Finalized | InputStatus::Finalizable => false,
+				_ => true,
+			})
+			.map(|i| i.index)
+			.collect()
+	}
+
+	/// Check if all inputs are finalized or can be finalized.
+	pub fn is_finalizable(&self) -> bool {
+		self.incomplete_inputs().is_empty()
+	}
+}
+
+impl fmt::Display for PsbtDiff {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for input in &self.inputs {
+			write!(f, "input {}: +{} signatures, ", input.index, input.new_signatures.len())?;
+			match input.status {
+				InputStatus::Finalized => writeln!(f, "finalized")?,
+				InputStatus::Finalizable => writeln!(f, "finalizable")?,
+				InputStatus::MissingSignatures(n) => writeln!(f, "{} signatures missing", n)?,
+				InputStatus::Unknown => {
+					writeln!(f, "{} signatures, status unknown", input.signatures)?
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Get the output script of the output spent by the PSBT input, if the PSBT has the UTXO.
+fn input_script_pubkey(psbt: &psbt::PartiallySignedTransaction, index: usize) -> Option<&Script> {
+	let input = &psbt.inputs[index];
+	match input.witness_utxo {
+		Some(ref txout) => Some(&txout.script_pubkey),
+		None => {
+			let vout = psbt.global.unsigned_tx.input.get(index)?.previous_output.vout;
+			input.non_witness_utxo.as_ref()?.output.get(vout as usize).map(|o| &o.script_pubkey)
+		}
+	}
+}
+
+/// Get the number of signatures required by the script that is signed for.
+fn required_signatures(script: &Script) -> Option<usize> {
+	if script.is_p2pkh() || script.is_v0_p2wpkh() {
+		return Some(1);
+	}
+	let bytes = script.as_bytes();
+	match (bytes.first(), bytes.last()) {
+		(Some(&m), Some(&OP_CHECKMULTISIG)) if m >= OP_1 && m <= OP_16 => {
+			Some((m - OP_1 + 1) as usize)
+		}
+		_ => None,
+	}
+}
+
+/// Determine the signing status of the PSBT input.
+fn input_status(psbt: &psbt::PartiallySignedTransaction, index: usize) -> InputStatus {
+	let input = &psbt.inputs[index];
+	if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+		return InputStatus::Finalized;
+	}
+
+	let script = match (input.witness_script.as_ref(), input.redeem_script.as_ref()) {
+		(Some(ws), _) => Some(ws),
+		(None, Some(rs)) => Some(rs),
+		(None, None) => input_script_pubkey(psbt, index),
+	};
+	match script.and_then(required_signatures) {
+		Some(required) if input.partial_sigs.len() >= required => InputStatus::Finalizable,
+		Some(required) => InputStatus::MissingSignatures(required - input.partial_sigs.len()),
+		None => InputStatus::Unknown,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bitcoin::{OutPoint, Transaction, TxIn, TxOut};
+	use hex;
+
+	const KEYS: [&str; 3] = [
+		"0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+		"02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+		"02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+	];
+
+	fn key(index: usize) -> PublicKey {
+		PublicKey::from_slice(&hex::decode(KEYS[index]).unwrap()).unwrap()
+	}
+
+	fn script(hex_script: &str) -> Script {
+		Script::from(hex::decode(hex_script).unwrap())
+	}
+
+	fn p2wpkh() -> Script {
+		script("00147557920fbc32a1ef4ef26bae5e8ce3f95abf09ce")
+	}
+
+	/// A 2-of-3 multisig script of the keys.
+	fn multisig() -> Script {
+		let mut bytes = vec![OP_1 + 1];
+		for index in 0..3 {
+			bytes.push(33);
+			bytes.extend(key(index).to_bytes());
+		}
+		bytes.push(OP_1 + 2);
+		bytes.push(OP_CHECKMULTISIG);
+		Script::from(bytes)
+	}
+
+	/// A PSBT with a single input and an output of the given amount.
+	fn psbt(amount: u64) -> psbt::PartiallySignedTransaction {
+		let tx = Transaction {
+			version: 2,
+			lock_time: 0,
+			input: vec![TxIn {
+				previous_output: OutPoint::default(),
+				script_sig: Script::new(),
+				sequence: 0xffffffff,
+				witness: Vec::new(),
+			}],
+			output: vec![TxOut {
+				value: amount,
+				script_pubkey: p2wpkh(),
+			}],
+		};
+		psbt::PartiallySignedTransaction::from_unsigned_tx(tx).unwrap()
+	}
+
+	/// Add a signature of the key to the first input.
+	fn sign(psbt: &mut psbt::PartiallySignedTransaction, index: usize) {
+		psbt.inputs[0].partial_sigs.insert(key(index), vec![0x30, index as u8]);
+	}
+
+	#[test]
+	fn required_signatures_of_scripts() {
+		assert_eq!(required_signatures(&p2wpkh()), Some(1));
+		let p2pkh = script("76a91498af0aaca388a7e1024f505c033626d908e3b54a88ac");
+		assert_eq!(required_signatures(&p2pkh), Some(1));
+		assert_eq!(required_signatures(&multisig()), Some(2));
+		assert_eq!(required_signatures(&script("6a0401020304")), None);
+	}
+
+	#[test]
+	fn single_key() {
+		let mut before = psbt(1000);
+		before.inputs[0].witness_utxo = Some(TxOut {
+			value: 2000,
+			script_pubkey: p2wpkh(),
+		});
+		let mut after = before.clone();
+		sign(&mut after, 0);
+
+		let diff = PsbtDiff::between(&before, &after).unwrap();
+		assert_eq!(
+			diff.inputs,
+			vec![InputDiff {
+				index: 0,
+				new_signatures: vec![key(0)],
+				signatures: 1,
+				status: InputStatus::Finalizable,
+			}]
+		);
+		assert_eq!(diff.signed_inputs(), vec![0]);
+		assert!(diff.is_finalizable());
+	}
+
+	#[test]
+	fn multisig_witness_script() {
+		let mut before = psbt(1000);
+		before.inputs[0].witness_script = Some(multisig());
+		sign(&mut before, 2);
+		let mut after = before.clone();
+		sign(&mut after, 0);
+
+		let diff = PsbtDiff::between(&before, &before).unwrap();
+		assert_eq!(diff.inputs[0].status, InputStatus::MissingSignatures(1));
+		assert!(diff.signed_inputs().is_empty());
+		assert_eq!(diff.incomplete_inputs(), vec![0]);
+
+		let diff = PsbtDiff::between(&before, &after).unwrap();
+		assert_eq!(diff.inputs[0].new_signatures, vec![key(0)]);
+		assert_eq!(diff.inputs[0].signatures, 2);
+		assert_eq!(diff.inputs[0].status, InputStatus::Finalizable);
+		assert!(diff.is_finalizable());
+	}
+
+	#[test]
+	fn finalized_input() {
+		let before = psbt(1000);
+		let mut after = before.clone();
+		after.inputs[0].final_script_witness = Some(vec![vec![0x30], key(0).to_bytes()]);
+
+		let diff = PsbtDiff::between(&before, &after).unwrap();
+		assert_eq!(diff.inputs[0].status, InputStatus::Finalized);
+		assert!(diff.inputs[0].new_signatures.is_empty());
+		assert!(diff.is_finalizable());
+	}
+
+	#[test]
+	fn missing_utxo() {
+		let before = psbt(1000);
+		let mut after = before.clone();
+		sign(&mut after, 0);
+
+		let diff = PsbtDiff::between(&before, &after).unwrap();
+		assert_eq!(diff.inputs[0].status, InputStatus::Unknown);
+		assert_eq!(diff.signed_inputs(), vec![0]);
+		assert_eq!(diff.incomplete_inputs(), vec![0]);
+		assert_eq!(diff.to_string(), "input 0: +1 signatures, 1 signatures, status unknown\n");
+	}
+
+	#[test]
+	fn mismatched_transaction() {
+		match PsbtDiff::between(&psbt(1000), &psbt(2000)) {
+			Err(Error::InvalidPsbt(_)) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+}
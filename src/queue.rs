@@ -0,0 +1,335 @@
+//! # Request queue
+//!
+//! A queue for sharing one device between multiple callers, f.e. in a signing service.  The
+//! device is owned by a worker thread that runs the queued operations one at a time, so the
+//! protocol messages of concurrent operations are never interleaved.  The callers take turns:
+//! the worker picks the operations of the waiting callers in round-robin order.  Optionally, a
+//! minimum interval between the start of two operations limits the rate.
+
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use client::Trezor;
+use error::{Error, Result};
+use {AvailableDevice, ConnectOptions};
+
+/// An operation queued for the device.
+type Job = Box<FnMut(&mut Trezor) + Send>;
+
+/// Metrics of a device queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMetrics {
+	/// The number of queued operations that didn't start yet.
+	pub depth: usize,
+	/// The number of callers with queued operations.
+	pub waiting_callers: usize,
+	/// The number of operations that have finished.
+	pub completed: u64,
+}
+
+struct State {
+	/// The callers with queued operations, in the order of their turn.
+	turns: VecDeque<String>,
+	jobs: HashMap<String, VecDeque<Job>>,
+	completed: u64,
+	shutdown: bool,
+}
+
+impl State {
+	/// Queue an operation for the caller.  A caller without queued operations gets the last turn.
+	fn push(&mut self, caller: &str, job: Job) {
+		if !self.jobs.contains_key(caller) {
+			self.turns.push_back(caller.to_owned());
+		}
+		self.jobs.entry(caller.to_owned()).or_insert_with(VecDeque::new).push_back(job);
+	}
+
+	/// Take the next operation of the caller whose turn it is.
+	fn next_job(&mut self) -> Option<Job> {
+		let caller = self.turns.pop_front()?;
+		let (job, more) = {
+			let queue = self.jobs.get_mut(&caller).expect("caller without jobs");
+			(queue.pop_front().expect("empty job queue"), !queue.is_empty())
+		};
+		if more {
+			self.turns.push_back(caller);
+		} else {
+			self.jobs.remove(&caller);
+		}
+		Some(job)
+	}
+}
+
+struct Shared {
+	state: Mutex<State>,
+	cond: Condvar,
+}
+
+/// A queue that serializes the operations on a device.  The device is connected and initialized
+/// on a worker thread that owns it until the queue is dropped.
+pub struct DeviceQueue {
+	shared: Arc<Shared>,
+	worker: Option<thread::JoinHandle<()>>,
+}
+
+impl DeviceQueue {
+	/// Connect to the device on a new worker thread and initialize it.  With `min_interval`,
+	/// operations are started at most once per interval.
+	pub fn spawn(
+		device: AvailableDevice,
+		options: ConnectOptions,
+		min_interval: Option<Duration>,
+	) -> Result<DeviceQueue> {
+		DeviceQueue::start(
+			move || {
+				let mut trezor = device.connect_with_options(options)?;
+				trezor.init_device()?;
+				Ok(trezor)
+			},
+			min_interval,
+		)
+	}
+
+	/// Start the worker thread with the device returned by the connect function.
+	fn start<C>(connect: C, min_interval: Option<Duration>) -> Result<DeviceQueue>
+	where
+		C: FnOnce() -> Result<Trezor> + Send + 'static,
+	{
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				turns: VecDeque::new(),
+				jobs: HashMap::new(),
+				completed: 0,
+				shutdown: false,
+			}),
+			cond: Condvar::new(),
+		});
+
+		let (init_tx, init_rx) = mpsc::channel();
+		let worker_shared = shared.clone();
+		let worker = thread::spawn(move || {
+			let mut trezor = match connect() {
+				Ok(trezor) => trezor,
+				Err(e) => return init_tx.send(Err(e)).unwrap_or(()),
+			};
+			let _ = init_tx.send(Ok(()));
+			run_worker(&worker_shared, &mut trezor, min_interval);
+		});
+		init_rx.recv().map_err(|_| Error::DeviceQueueClosed)??;
+
+		Ok(DeviceQueue {
+			shared: shared,
+			worker: Some(worker),
+		})
+	}
+
+	/// Queue the operation for the caller and wait for its result.  Callers are identified by
+	/// the given name and take turns when multiple callers are waiting.
+	pub fn run<T, F>(&self, caller: &str, op: F) -> Result<T>
+	where
+		T: Send + 'static,
+		F: FnOnce(&mut Trezor) -> Result<T> + Send + 'static,
+	{
+		let (tx, rx) = mpsc::channel();
+		let mut op = Some(op);
+		let job: Job = Box::new(move |trezor| {
+			if let Some(op) = op.take() {
+				// The caller might have given up on us already.
+				let _ = tx.send(op(trezor));
+			}
+		});
+
+		{
+			let mut state = self.shared.state.lock().expect("poisoned queue lock");
+			if state.shutdown {
+				return Err(Error::DeviceQueueClosed);
+			}
+			state.push(caller, job);
+		}
+		self.shared.cond.notify_one();
+
+		rx.recv().map_err(|_| Error::DeviceQueueClosed)?
+	}
+
+	/// Get the current metrics of the queue.
+	pub fn metrics(&self) -> QueueMetrics {
+		let state = self.shared.state.lock().expect("poisoned queue lock");
+		QueueMetrics {
+			depth: state.jobs.values().map(|q| q.len()).sum(),
+			waiting_callers: state.turns.len(),
+			completed: state.completed,
+		}
+	}
+}
+
+impl Drop for DeviceQueue {
+	fn drop(&mut self) {
+		self.shared.state.lock().expect("poisoned queue lock").shutdown = true;
+		self.shared.cond.notify_all();
+		if let Some(worker) = self.worker.take() {
+			let _ = worker.join();
+		}
+	}
+}
+
+/// Run the queued operations until the queue is shut down.
+fn run_worker(shared: &Shared, trezor: &mut Trezor, min_interval: Option<Duration>) {
+	let mut last_start: Option<Instant> = None;
+	loop {
+		let mut job = {
+			let mut state = shared.state.lock().expect("poisoned queue lock");
+			loop {
+				if state.shutdown {
+					// Dropping the remaining jobs lets their callers know.
+					state.turns.clear();
+					state.jobs.clear();
+					return;
+				}
+				if let Some(job) = state.next_job() {
+					break job;
+				}
+				state = shared.cond.wait(state).expect("poisoned queue lock");
+			}
+		};
+
+		if let (Some(interval), Some(last)) = (min_interval, last_start) {
+			let elapsed = last.elapsed();
+			if elapsed < interval {
+				thread::sleep(interval - elapsed);
+			}
+		}
+		last_start = Some(Instant::now());
+		// The result channel of a panicking operation is dropped with the job, so its caller
+		// gets an error.  The operation might have left the device in the middle of a request, so
+		// it is reset before the next operation.  If that fails, the queue is shut down.
+		if panic::catch_unwind(AssertUnwindSafe(|| job(trezor))).is_err() {
+			warn!("A queued operation panicked, resetting the device");
+			if let Err(e) = trezor.recover() {
+				warn!("Failed to reset the device, closing the queue: {}", e);
+				shared.state.lock().expect("poisoned queue lock").shutdown = true;
+			}
+		}
+		shared.state.lock().expect("poisoned queue lock").completed += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use protos;
+	use testing::{self, FaultPlan, ScriptedLink};
+	use Model;
+
+	fn scripted_trezor() -> Trezor {
+		testing::scripted_trezor(Model::Trezor2, ScriptedLink::new(), FaultPlan::new())
+	}
+
+	fn state() -> State {
+		State {
+			turns: VecDeque::new(),
+			jobs: HashMap::new(),
+			completed: 0,
+			shutdown: false,
+		}
+	}
+
+	#[test]
+	fn round_robin() {
+		let order = Arc::new(Mutex::new(Vec::new()));
+		let mut state = state();
+		for &(caller, job) in &[("a", "a1"), ("a", "a2"), ("a", "a3"), ("b", "b1"), ("b", "b2")] {
+			let order = order.clone();
+			state.push(caller, Box::new(move |_| order.lock().unwrap().push(job)));
+		}
+
+		let mut trezor = scripted_trezor();
+		while let Some(mut job) = state.next_job() {
+			job(&mut trezor);
+		}
+		assert_eq!(*order.lock().unwrap(), vec!["a1", "b1", "a2", "b2", "a3"]);
+		assert!(state.turns.is_empty());
+		assert!(state.jobs.is_empty());
+	}
+
+	#[test]
+	fn shutdown_drops_queued_jobs() {
+		let (tx, rx) = mpsc::channel::<()>();
+		let shared = Shared {
+			state: Mutex::new(state()),
+			cond: Condvar::new(),
+		};
+		{
+			let mut state = shared.state.lock().unwrap();
+			state.push("a", Box::new(move |_| tx.send(()).unwrap()));
+			state.shutdown = true;
+		}
+
+		run_worker(&shared, &mut scripted_trezor(), None);
+		assert!(rx.recv().is_err());
+		assert_eq!(shared.state.lock().unwrap().jobs.len(), 0);
+	}
+
+	/// An operation that panics while the device waits for a button.
+	fn panic_mid_request(trezor: &mut Trezor) -> Result<()> {
+		trezor.ping("")?;
+		panic!("operation failed")
+	}
+
+	fn cancelled() -> protos::Failure {
+		let mut failure = protos::Failure::new();
+		failure.set_code(protos::Failure_FailureType::Failure_ActionCancelled);
+		failure
+	}
+
+	#[test]
+	fn panicking_operation() {
+		let queue = DeviceQueue::start(
+			|| {
+				let link = ScriptedLink::new();
+				link.push_message(&protos::ButtonRequest::new());
+				// The answers to Cancel and Initialize and then to the next operation.
+				link.push_message(&cancelled());
+				link.push_message(&protos::Features::new());
+				link.push_message(&protos::Success::new());
+				Ok(testing::scripted_trezor(Model::Trezor2, link, FaultPlan::new()))
+			},
+			None,
+		)
+		.unwrap();
+		match queue.run("a", panic_mid_request) {
+			Err(Error::DeviceQueueClosed) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+		let initialized = queue
+			.run("a", |t| {
+				t.ping("")?.ok()?;
+				Ok(t.features().is_some())
+			})
+			.unwrap();
+		assert!(initialized);
+	}
+
+	#[test]
+	fn unrecoverable_panic() {
+		let queue = DeviceQueue::start(
+			|| {
+				let link = ScriptedLink::new();
+				link.push_message(&protos::ButtonRequest::new());
+				// The device doesn't answer Initialize.
+				link.push_message(&cancelled());
+				Ok(testing::scripted_trezor(Model::Trezor2, link, FaultPlan::new()))
+			},
+			None,
+		)
+		.unwrap();
+		assert!(queue.run("a", panic_mid_request).is_err());
+		match queue.run("a", |_| Ok(1)) {
+			Err(Error::DeviceQueueClosed) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+}
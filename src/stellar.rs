@@ -0,0 +1,551 @@
+//! # Stellar
+//!
+//! Types to describe Stellar transactions to be signed by the device.  Use them with the
+//! `Trezor::stellar_sign_tx` method.
+
+use error::{Error, Result};
+use protos;
+
+/// The maximum length in bytes of a text memo.
+const MEMO_TEXT_MAX_LEN: usize = 28;
+
+/// The length in bytes of a hash memo.
+const MEMO_HASH_LEN: usize = 32;
+
+/// The network passphrase of the public Stellar network.
+pub const PUBLIC_NETWORK_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+
+/// The network passphrase of the Stellar test network.
+pub const TESTNET_NETWORK_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+
+/// A Stellar asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Asset {
+	/// The native asset, lumens (XLM).
+	Native,
+	/// An asset with a code of at most 4 characters.
+	Alphanum4 {
+		code: String,
+		issuer: String,
+	},
+	/// An asset with a code of at most 12 characters.
+	Alphanum12 {
+		code: String,
+		issuer: String,
+	},
+}
+
+impl Asset {
+	fn to_proto(&self) -> protos::StellarAssetType {
+		let mut asset = protos::StellarAssetType::new();
+		match *self {
+			Asset::Native => asset.set_field_type(0),
+			Asset::Alphanum4 {
+				ref code,
+				ref issuer,
+			} => {
+				asset.set_field_type(1);
+				asset.set_code(code.clone());
+				asset.set_issuer(issuer.clone());
+			}
+			Asset::Alphanum12 {
+				ref code,
+				ref issuer,
+			} => {
+				asset.set_field_type(2);
+				asset.set_code(code.clone());
+				asset.set_issuer(issuer.clone());
+			}
+		}
+		asset
+	}
+}
+
+/// The memo attached to a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Memo {
+	None,
+	/// A text of at most 28 bytes.
+	Text(String),
+	Id(u64),
+	/// A 32-byte hash.
+	Hash(Vec<u8>),
+	/// The 32-byte hash of the transaction that is refunded.
+	Return(Vec<u8>),
+}
+
+/// A signer to add, update or remove with a `SetOptions` operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerKey {
+	/// An ed25519 public key.
+	Ed25519(Vec<u8>),
+	/// The hash of a pre-authorized transaction.
+	PreAuthTx(Vec<u8>),
+	/// The hash of a preimage that has to be revealed to sign.
+	HashX(Vec<u8>),
+}
+
+/// The different Stellar operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationBody {
+	/// Create and fund a new account.
+	CreateAccount {
+		destination: String,
+		starting_balance: i64,
+	},
+	/// Send an amount of an asset to an account.
+	Payment {
+		destination: String,
+		asset: Asset,
+		amount: i64,
+	},
+	/// Send an amount of an asset to an account, converting it along the given path of assets.
+	PathPayment {
+		send_asset: Asset,
+		send_max: i64,
+		destination: String,
+		destination_asset: Asset,
+		destination_amount: i64,
+		path: Vec<Asset>,
+	},
+	/// Create, update or delete an offer.  An offer ID of 0 creates a new offer and an amount of
+	/// 0 deletes the offer.
+	ManageOffer {
+		selling: Asset,
+		buying: Asset,
+		amount: i64,
+		/// The price as (numerator, denominator).
+		price: (u32, u32),
+		offer_id: u64,
+	},
+	/// Create an offer that doesn't take offers at the same price.
+	CreatePassiveOffer {
+		selling: Asset,
+		buying: Asset,
+		amount: i64,
+		/// The price as (numerator, denominator).
+		price: (u32, u32),
+	},
+	/// Change the options of the account.  Options that are None are left unchanged.
+	SetOptions {
+		inflation_destination: Option<String>,
+		clear_flags: Option<u32>,
+		set_flags: Option<u32>,
+		master_weight: Option<u32>,
+		low_threshold: Option<u32>,
+		medium_threshold: Option<u32>,
+		high_threshold: Option<u32>,
+		home_domain: Option<String>,
+		/// The signer and its weight.  A weight of 0 removes the signer.
+		signer: Option<(SignerKey, u32)>,
+	},
+	/// Create, update or delete a trustline.  A limit of 0 deletes the trustline.
+	ChangeTrust {
+		asset: Asset,
+		limit: u64,
+	},
+	/// Authorize or deauthorize an account to hold an asset issued by the source account.
+	AllowTrust {
+		trustor: String,
+		asset_code: String,
+		authorize: bool,
+	},
+	/// Merge the account into the destination account.
+	AccountMerge {
+		destination: String,
+	},
+	/// Set, update or delete a data entry of the account.  A value of None deletes the entry.
+	ManageData {
+		name: String,
+		value: Option<Vec<u8>>,
+	},
+	/// Bump the sequence number of the account.
+	BumpSequence {
+		bump_to: u64,
+	},
+}
+
+/// A Stellar operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+	/// The source account of the operation if it's different from the transaction's.
+	pub source_account: Option<String>,
+	pub body: OperationBody,
+}
+
+/// A Stellar transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+	/// The passphrase of the network the transaction is for, see `PUBLIC_NETWORK_PASSPHRASE`.
+	pub network_passphrase: String,
+	pub source_account: String,
+	/// The fee in stroops.
+	pub fee: u32,
+	pub sequence_number: u64,
+	/// The time bounds as (min time, max time) UNIX timestamps.
+	pub time_bounds: Option<(u32, u32)>,
+	pub memo: Memo,
+	pub operations: Vec<Operation>,
+}
+
+impl Transaction {
+	/// Create the StellarSignTx message for the transaction.  The memo is checked on the host, so
+	/// an invalid memo is rejected before anything is sent to the device.
+	pub(crate) fn to_sign_tx(&self, path: Vec<u32>) -> Result<protos::StellarSignTx> {
+		let mut req = protos::StellarSignTx::new();
+		req.set_address_n(path);
+		req.set_network_passphrase(self.network_passphrase.clone());
+		req.set_source_account(self.source_account.clone());
+		req.set_fee(self.fee);
+		req.set_sequence_number(self.sequence_number);
+		if let Some((start, end)) = self.time_bounds {
+			req.set_timebounds_start(start);
+			req.set_timebounds_end(end);
+		}
+		match self.memo {
+			Memo::None => req.set_memo_type(0),
+			Memo::Text(ref text) => {
+				if text.len() > MEMO_TEXT_MAX_LEN {
+					return Err(Error::InvalidStellarTransaction(format!(
+						"text memo of {} bytes, at most {} are allowed",
+						text.len(),
+						MEMO_TEXT_MAX_LEN
+					)));
+				}
+				req.set_memo_type(1);
+				req.set_memo_text(text.clone());
+			}
+			Memo::Id(id) => {
+				req.set_memo_type(2);
+				req.set_memo_id(id);
+			}
+			Memo::Hash(ref hash) => {
+				check_memo_hash(hash)?;
+				req.set_memo_type(3);
+				req.set_memo_hash(hash.clone());
+			}
+			Memo::Return(ref hash) => {
+				check_memo_hash(hash)?;
+				req.set_memo_type(4);
+				req.set_memo_hash(hash.clone());
+			}
+		}
+		req.set_num_operations(self.operations.len() as u32);
+		Ok(req)
+	}
+}
+
+/// Check that the hash of a memo has the right length.
+fn check_memo_hash(hash: &[u8]) -> Result<()> {
+	if hash.len() != MEMO_HASH_LEN {
+		return Err(Error::InvalidStellarTransaction(format!(
+			"hash memo of {} bytes instead of {}",
+			hash.len(),
+			MEMO_HASH_LEN
+		)));
+	}
+	Ok(())
+}
+
+/// The message for a single operation, as sent to the device.
+pub(crate) enum OperationMessage {
+	CreateAccount(protos::StellarCreateAccountOp),
+	Payment(protos::StellarPaymentOp),
+	PathPayment(protos::StellarPathPaymentOp),
+	ManageOffer(protos::StellarManageOfferOp),
+	CreatePassiveOffer(protos::StellarCreatePassiveOfferOp),
+	SetOptions(protos::StellarSetOptionsOp),
+	ChangeTrust(protos::StellarChangeTrustOp),
+	AllowTrust(protos::StellarAllowTrustOp),
+	AccountMerge(protos::StellarAccountMergeOp),
+	ManageData(protos::StellarManageDataOp),
+	BumpSequence(protos::StellarBumpSequenceOp),
+}
+
+impl Operation {
+	/// Create the message for the operation.
+	pub(crate) fn to_message(&self) -> OperationMessage {
+		match self.body {
+			OperationBody::CreateAccount {
+				ref destination,
+				starting_balance,
+			} => {
+				let mut op = protos::StellarCreateAccountOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_new_account(destination.clone());
+				op.set_starting_balance(starting_balance);
+				OperationMessage::CreateAccount(op)
+			}
+			OperationBody::Payment {
+				ref destination,
+				ref asset,
+				amount,
+			} => {
+				let mut op = protos::StellarPaymentOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_destination_account(destination.clone());
+				op.set_asset(asset.to_proto());
+				op.set_amount(amount);
+				OperationMessage::Payment(op)
+			}
+			OperationBody::PathPayment {
+				ref send_asset,
+				send_max,
+				ref destination,
+				ref destination_asset,
+				destination_amount,
+				ref path,
+			} => {
+				let mut op = protos::StellarPathPaymentOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_send_asset(send_asset.to_proto());
+				op.set_send_max(send_max);
+				op.set_destination_account(destination.clone());
+				op.set_destination_asset(destination_asset.to_proto());
+				op.set_destination_amount(destination_amount);
+				op.set_paths(path.iter().map(|a| a.to_proto()).collect());
+				OperationMessage::PathPayment(op)
+			}
+			OperationBody::ManageOffer {
+				ref selling,
+				ref buying,
+				amount,
+				price,
+				offer_id,
+			} => {
+				let mut op = protos::StellarManageOfferOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_selling_asset(selling.to_proto());
+				op.set_buying_asset(buying.to_proto());
+				op.set_amount(amount);
+				op.set_price_n(price.0);
+				op.set_price_d(price.1);
+				op.set_offer_id(offer_id);
+				OperationMessage::ManageOffer(op)
+			}
+			OperationBody::CreatePassiveOffer {
+				ref selling,
+				ref buying,
+				amount,
+				price,
+			} => {
+				let mut op = protos::StellarCreatePassiveOfferOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_selling_asset(selling.to_proto());
+				op.set_buying_asset(buying.to_proto());
+				op.set_amount(amount);
+				op.set_price_n(price.0);
+				op.set_price_d(price.1);
+				OperationMessage::CreatePassiveOffer(op)
+			}
+			OperationBody::SetOptions {
+				ref inflation_destination,
+				clear_flags,
+				set_flags,
+				master_weight,
+				low_threshold,
+				medium_threshold,
+				high_threshold,
+				ref home_domain,
+				ref signer,
+			} => {
+				let mut op = protos::StellarSetOptionsOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				if let Some(ref d) = *inflation_destination {
+					op.set_inflation_destination_account(d.clone());
+				}
+				if let Some(f) = clear_flags {
+					op.set_clear_flags(f);
+				}
+				if let Some(f) = set_flags {
+					op.set_set_flags(f);
+				}
+				if let Some(w) = master_weight {
+					op.set_master_weight(w);
+				}
+				if let Some(t) = low_threshold {
+					op.set_low_threshold(t);
+				}
+				if let Some(t) = medium_threshold {
+					op.set_medium_threshold(t);
+				}
+				if let Some(t) = high_threshold {
+					op.set_high_threshold(t);
+				}
+				if let Some(ref d) = *home_domain {
+					op.set_home_domain(d.clone());
+				}
+				if let Some((ref key, weight)) = *signer {
+					let (signer_type, key) = match *key {
+						SignerKey::Ed25519(ref k) => (0, k),
+						SignerKey::PreAuthTx(ref k) => (1, k),
+						SignerKey::HashX(ref k) => (2, k),
+					};
+					op.set_signer_type(signer_type);
+					op.set_signer_key(key.clone());
+					op.set_signer_weight(weight);
+				}
+				OperationMessage::SetOptions(op)
+			}
+			OperationBody::ChangeTrust {
+				ref asset,
+				limit,
+			} => {
+				let mut op = protos::StellarChangeTrustOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_asset(asset.to_proto());
+				op.set_limit(limit);
+				OperationMessage::ChangeTrust(op)
+			}
+			OperationBody::AllowTrust {
+				ref trustor,
+				ref asset_code,
+				authorize,
+			} => {
+				let mut op = protos::StellarAllowTrustOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_trusted_account(trustor.clone());
+				op.set_asset_type(if asset_code.len() <= 4 {
+					1
+				} else {
+					2
+				});
+				op.set_asset_code(asset_code.clone());
+				op.set_is_authorized(authorize as u32);
+				OperationMessage::AllowTrust(op)
+			}
+			OperationBody::AccountMerge {
+				ref destination,
+			} => {
+				let mut op = protos::StellarAccountMergeOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_destination_account(destination.clone());
+				OperationMessage::AccountMerge(op)
+			}
+			OperationBody::ManageData {
+				ref name,
+				ref value,
+			} => {
+				let mut op = protos::StellarManageDataOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_key(name.clone());
+				if let Some(ref v) = *value {
+					op.set_value(v.clone());
+				}
+				OperationMessage::ManageData(op)
+			}
+			OperationBody::BumpSequence {
+				bump_to,
+			} => {
+				let mut op = protos::StellarBumpSequenceOp::new();
+				if let Some(ref s) = self.source_account {
+					op.set_source_account(s.clone());
+				}
+				op.set_bump_to(bump_to);
+				OperationMessage::BumpSequence(op)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SOURCE: &str = "GAK5MSF74TJW6GLM7NLTL76YZJKM2S4CGP3UH4REJHPHZ4YBZW2GSBPW";
+	const DESTINATION: &str = "GBOVKZBEM2YYLOCDCUXJ4IMRKHN4LCJAE7WEAEA2KF562XFAGDBOB64V";
+
+	fn transaction(memo: Memo) -> Transaction {
+		Transaction {
+			network_passphrase: TESTNET_NETWORK_PASSPHRASE.to_owned(),
+			source_account: SOURCE.to_owned(),
+			fee: 100,
+			sequence_number: 1000,
+			time_bounds: None,
+			memo: memo,
+			operations: vec![Operation {
+				source_account: None,
+				body: OperationBody::Payment {
+					destination: DESTINATION.to_owned(),
+					asset: Asset::Alphanum4 {
+						code: "X".to_owned(),
+						issuer: SOURCE.to_owned(),
+					},
+					amount: 500111000,
+				},
+			}],
+		}
+	}
+
+	#[test]
+	fn payment() {
+		let tx = transaction(Memo::None);
+		let req = tx.to_sign_tx(vec![0x8000002c, 0x80000094, 0x80000000]).unwrap();
+		assert_eq!(req.get_network_passphrase(), TESTNET_NETWORK_PASSPHRASE);
+		assert_eq!(req.get_source_account(), SOURCE);
+		assert_eq!(req.get_memo_type(), 0);
+		assert_eq!(req.get_num_operations(), 1);
+
+		let op = match tx.operations[0].to_message() {
+			OperationMessage::Payment(op) => op,
+			_ => panic!("not a payment"),
+		};
+		assert!(!op.has_source_account());
+		assert_eq!(op.get_destination_account(), DESTINATION);
+		assert_eq!(op.get_asset().get_field_type(), 1);
+		assert_eq!(op.get_asset().get_code(), "X");
+		assert_eq!(op.get_asset().get_issuer(), SOURCE);
+		assert_eq!(op.get_amount(), 500111000);
+	}
+
+	#[test]
+	fn memos() {
+		let req = transaction(Memo::Text("a".repeat(28))).to_sign_tx(Vec::new()).unwrap();
+		assert_eq!(req.get_memo_type(), 1);
+		assert_eq!(req.get_memo_text(), "a".repeat(28));
+
+		let req = transaction(Memo::Id(123)).to_sign_tx(Vec::new()).unwrap();
+		assert_eq!(req.get_memo_type(), 2);
+		assert_eq!(req.get_memo_id(), 123);
+
+		let req = transaction(Memo::Hash(vec![1; 32])).to_sign_tx(Vec::new()).unwrap();
+		assert_eq!(req.get_memo_type(), 3);
+		assert_eq!(req.get_memo_hash(), &[1; 32][..]);
+
+		let req = transaction(Memo::Return(vec![2; 32])).to_sign_tx(Vec::new()).unwrap();
+		assert_eq!(req.get_memo_type(), 4);
+		assert_eq!(req.get_memo_hash(), &[2; 32][..]);
+	}
+
+	#[test]
+	fn invalid_memos() {
+		// The limit is in bytes, not characters.
+		let text = "\u{e9}".repeat(15);
+		for memo in vec![Memo::Text(text), Memo::Hash(vec![1; 31]), Memo::Return(vec![2; 33])] {
+			match transaction(memo).to_sign_tx(Vec::new()) {
+				Err(Error::InvalidStellarTransaction(_)) => {}
+				r => panic!("unexpected result: {:?}", r),
+			}
+		}
+	}
+}
@@ -0,0 +1,287 @@
+//! # Testing
+//!
+//! Helpers for integration tests against the emulator or devices running debug firmware.  They
+//! use the standard test seed from SLIP-14 so that tests in different projects can share
+//! fixtures and known-answer vectors.
+//!
+//! The helpers are only available with the `testing` feature.
+//!
+//! A `FaultPlan` can be passed to `AvailableDevice::connect_with_faults()` to inject faults in the communication
+//! with the device, so that error paths can be exercised deterministically.  Without a device,
+//! `scripted_trezor()` creates a client that talks to a `ScriptedLink` instead.
+
+use std::time::Duration;
+
+use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
+use bitcoin::util::bip32;
+use hex;
+
+use client::{self, Trezor};
+use error::{Error, Result};
+use interaction::InteractionHandler;
+use transport::scripted::ScriptedTransport;
+use types::InputScriptType;
+use utils;
+use Model;
+
+pub use transport::scripted::ScriptedLink;
+
+/// The SLIP-14 test mnemonic.
+pub const SLIP14_MNEMONIC: &str = "all all all all all all all all all all all all";
+
+/// The label set on the device when loading the SLIP-14 seed.
+pub const SLIP14_LABEL: &str = "SLIP-0014";
+
+/// The fingerprint of the SLIP-14 master key.
+pub const SLIP14_FINGERPRINT: [u8; 4] = [0x5c, 0x9e, 0x22, 0x8d];
+
+/// The known account xpubs of the SLIP-14 seed without passphrase on mainnet.  The xpubs use the
+/// standard xpub version bytes for all script types.
+pub const SLIP14_XPUBS: [(&[u32], InputScriptType, &str); 3] = [
+	(
+		&[44 | utils::HARDENED, utils::HARDENED, utils::HARDENED],
+		InputScriptType::SpendAddress,
+		"xpub6BiVtCpG9fQPxnPmHXG8PhtzQdWC2Su4qWu6XW9tpWFYhxydCLJGrWBJZ5H6qTAHdPQ7pQhtpjiYZVZARo14qHiay2fvrX996oEP42u8wZy",
+	),
+	(
+		&[49 | utils::HARDENED, utils::HARDENED, utils::HARDENED],
+		InputScriptType::SpendP2shWitness,
+		"xpub6CVKsQYXc9awxgV1tWbG4foDvdcnieK2JkbpPEBKB5WwAPKBZ1mstLbKVB4ov7QzxzjaxNK6EfmNY5Jsk2cG26EVcEkycGW4tchT2dyUhrx",
+	),
+	(
+		&[84 | utils::HARDENED, utils::HARDENED, utils::HARDENED],
+		InputScriptType::SpendWitness,
+		"xpub6DDUPHpUo4pcy43iJeZjbSVWGav1SMMmuWdMHiGtkK8rhKmfbomtkwW6GKs1GGAKehT6QRocrmda3WWxXawpjmwaUHfFRXuKrXSapdckEYF",
+	),
+];
+
+/// The known first receive addresses of the SLIP-14 seed without passphrase on mainnet.
+pub const SLIP14_ADDRESSES: [(&[u32], InputScriptType, &str); 3] = [
+	(
+		&[44 | utils::HARDENED, utils::HARDENED, utils::HARDENED, 0, 0],
+		InputScriptType::SpendAddress,
+		"1JAd7XCBzGudGpJQSDSfpmJhiygtLQWaGL",
+	),
+	(
+		&[49 | utils::HARDENED, utils::HARDENED, utils::HARDENED, 0, 0],
+		InputScriptType::SpendP2shWitness,
+		"3L6TyTisPBmrDAj6RoKmDzNnj4eQi54gD2",
+	),
+	(
+		&[84 | utils::HARDENED, utils::HARDENED, utils::HARDENED, 0, 0],
+		InputScriptType::SpendWitness,
+		"bc1qannfxke2tfd4l7vhepehpvt05y83v3qsf6nfkk",
+	),
+];
+
+/// Convert a path from the test vectors.
+fn to_derivation_path(path: &[u32]) -> bip32::DerivationPath {
+	path.iter().map(|i| bip32::ChildNumber::from(*i)).collect::<Vec<_>>().into()
+}
+
+/// Load the SLIP-14 seed onto the device without PIN and passphrase and check the known
+/// fingerprint, xpubs and addresses.  Only the emulator and devices running debug firmware
+/// support loading a seed, and the device must be wiped first.  The device ID is random for every
+/// device, so it can't be checked.  The interaction requests are resolved with the handler.
+///
+/// Returns `Error::TestVectorMismatch` if the device returns a different fingerprint, xpub or
+/// address than the known-answer vectors.
+pub fn load_slip14_seed<H: InteractionHandler + ?Sized>(
+	trezor: &mut Trezor,
+	handler: &mut H,
+) -> Result<()> {
+	trezor
+		.load_device(SLIP14_MNEMONIC.to_owned(), None, false, SLIP14_LABEL.to_owned(), false)?
+		.interact(handler)?;
+	trezor.init_device()?;
+
+	// The master fingerprint is the parent fingerprint of any key at depth one.
+	let purpose = trezor
+		.get_public_key(
+			&to_derivation_path(&[44 | utils::HARDENED]),
+			InputScriptType::SpendAddress,
+			Network::Bitcoin,
+			false,
+		)?
+		.interact(handler)?;
+	if purpose.parent_fingerprint[..] != SLIP14_FINGERPRINT[..] {
+		return Err(Error::TestVectorMismatch(format!(
+			"fingerprint {} for SLIP-14 seed",
+			hex::encode(&purpose.parent_fingerprint[..])
+		)));
+	}
+
+	for &(path, script_type, expected) in SLIP14_XPUBS.iter() {
+		let xpub = trezor
+			.get_public_key(&to_derivation_path(path), script_type, Network::Bitcoin, false)?
+			.interact(handler)?;
+		if xpub.to_string() != expected {
+			return Err(Error::TestVectorMismatch(format!(
+				"xpub {} for SLIP-14 seed at {}",
+				xpub,
+				utils::path_to_string(path)
+			)));
+		}
+	}
+	for &(path, script_type, expected) in SLIP14_ADDRESSES.iter() {
+		let address = trezor
+			.get_address(&to_derivation_path(path), script_type, Network::Bitcoin, false)?
+			.interact(handler)?;
+		if address.to_string() != expected {
+			return Err(Error::TestVectorMismatch(format!(
+				"address {} for SLIP-14 seed at {}",
+				address,
+				utils::path_to_string(path)
+			)));
+		}
+	}
+	Ok(())
+}
+
+/// A fault to inject in a chunk sent to or received from the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+	/// Drop the chunk.  A written chunk is not sent and a read chunk is discarded.
+	DropChunk,
+	/// Corrupt the magic byte at the start of the chunk.
+	CorruptMagic,
+	/// Delay the chunk.
+	Delay(Duration),
+	/// Fail as if the device was disconnected.  All later chunks fail too.
+	Disconnect,
+}
+
+/// The direction of a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkDirection {
+	/// A chunk received from the device.
+	Read,
+	/// A chunk sent to the device.
+	Write,
+}
+
+/// The faults to inject in the communication with a device.  The chunks are counted from zero
+/// for each direction, starting when the device is connected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaultPlan {
+	faults: Vec<(ChunkDirection, usize, Fault)>,
+}
+
+impl FaultPlan {
+	/// Create a plan without faults.
+	pub fn new() -> FaultPlan {
+		FaultPlan::default()
+	}
+
+	/// Inject the fault in the given chunk received from the device.
+	pub fn on_read(mut self, chunk: usize, fault: Fault) -> FaultPlan {
+		self.faults.push((ChunkDirection::Read, chunk, fault));
+		self
+	}
+
+	/// Inject the fault in the given chunk sent to the device.
+	pub fn on_write(mut self, chunk: usize, fault: Fault) -> FaultPlan {
+		self.faults.push((ChunkDirection::Write, chunk, fault));
+		self
+	}
+
+	/// Get the fault to inject in the given chunk, if any.
+	pub(crate) fn fault(&self, direction: ChunkDirection, chunk: usize) -> Option<Fault> {
+		self.faults.iter().find(|f| f.0 == direction && f.1 == chunk).map(|f| f.2)
+	}
+}
+
+/// Create a client that talks to the scripted link instead of a device.  The faults of the plan
+/// are injected in the communication.
+pub fn scripted_trezor(model: Model, link: ScriptedLink, faults: FaultPlan) -> Trezor {
+	client::trezor_with_transport(model, Box::new(ScriptedTransport::new(link, faults)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use protobuf;
+	use protos;
+	use std::panic;
+	use std::time::Instant;
+	use transport;
+
+	fn success(message: &str) -> protos::Success {
+		let mut success = protos::Success::new();
+		success.set_message(message.to_owned());
+		success
+	}
+
+	/// Ping the device and return the message of the response.
+	fn ping(trezor: &mut Trezor) -> Result<String> {
+		let resp = trezor.call_raw(protos::Ping::new())?;
+		let success: protos::Success = protobuf::parse_from_bytes(resp.payload()).unwrap();
+		Ok(success.get_message().to_owned())
+	}
+
+	#[test]
+	fn dropped_read_chunk() {
+		let link = ScriptedLink::new();
+		link.push_message(&success("first"));
+		link.push_message(&success("second"));
+		let faults = FaultPlan::new().on_read(0, Fault::DropChunk);
+		let mut trezor = scripted_trezor(Model::Trezor2, link, faults);
+		assert_eq!(ping(&mut trezor).unwrap(), "second");
+	}
+
+	#[test]
+	fn dropped_write_chunk() {
+		let link = ScriptedLink::new();
+		link.push_message(&success("first"));
+		link.push_message(&success("second"));
+		let faults = FaultPlan::new().on_write(0, Fault::DropChunk);
+		let mut trezor = scripted_trezor(Model::Trezor2, link.clone(), faults);
+		assert_eq!(ping(&mut trezor).unwrap(), "first");
+		assert!(link.take_writes().is_empty());
+		assert_eq!(ping(&mut trezor).unwrap(), "second");
+		assert_eq!(link.take_writes().len(), 1);
+	}
+
+	#[test]
+	fn corrupted_magic() {
+		let link = ScriptedLink::new();
+		link.push_message(&success("first"));
+		let faults = FaultPlan::new().on_read(0, Fault::CorruptMagic);
+		let mut trezor = scripted_trezor(Model::Trezor2, link, faults);
+		match ping(&mut trezor) {
+			Err(Error::TransportReceiveMessage(transport::error::Error::DeviceBadMagic)) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+
+	#[test]
+	fn deadline_restored_after_panic() {
+		let link = ScriptedLink::new();
+		link.push_message(&success("first"));
+		let mut trezor = scripted_trezor(Model::Trezor2, link, FaultPlan::new());
+		let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+			trezor.run_with_deadline(Instant::now(), |_| -> Result<()> { panic!("flow panicked") })
+		}));
+		assert!(result.is_err());
+		// The deadline of the panicked flow no longer applies.
+		assert_eq!(ping(&mut trezor).unwrap(), "first");
+	}
+
+	#[test]
+	fn disconnect() {
+		let link = ScriptedLink::new();
+		link.push_message(&success("first"));
+		link.push_message(&success("second"));
+		let faults = FaultPlan::new().on_read(0, Fault::Disconnect);
+		let mut trezor = scripted_trezor(Model::Trezor2, link, faults);
+		match ping(&mut trezor) {
+			Err(Error::TransportReceiveMessage(transport::error::Error::DeviceDisconnected)) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+		// The device stays disconnected.
+		match ping(&mut trezor) {
+			Err(Error::TransportSendMessage(transport::error::Error::DeviceDisconnected)) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+}
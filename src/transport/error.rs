@@ -2,6 +2,7 @@
 
 use std::error;
 use std::fmt;
+use std::io;
 
 use hid;
 use libusb;
@@ -13,6 +14,8 @@ pub enum Error {
 	Hid(hid::Error),
 	/// Error from libusb.
 	Usb(libusb::Error),
+	/// IO error, f.e. from the UDP socket to the emulator.
+	Io(io::Error),
 	/// The device to connect to was not found.
 	DeviceNotFound,
 	/// The device is no longer available.
@@ -23,6 +26,8 @@ pub enum Error {
 	UnexpectedChunkSizeFromDevice(usize),
 	/// Timeout expired while reading from device.
 	DeviceReadTimeout,
+	/// Timeout expired while writing to device.
+	DeviceWriteTimeout,
 	/// The device sent a chunk with a wrong magic value.
 	DeviceBadMagic,
 	/// The device sent a message with a wrong session id.
@@ -31,6 +36,8 @@ pub enum Error {
 	DeviceUnexpectedSequenceNumber,
 	/// Received a non-existing message type from the device.
 	InvalidMessageType(u32),
+	/// The device announced a message larger than the maximum message size.
+	MessageTooLarge(usize),
 	/// Unable to determine device serial number.
 	NoDeviceSerial,
 }
@@ -47,11 +54,18 @@ impl From<libusb::Error> for Error {
 	}
 }
 
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::Io(e)
+	}
+}
+
 impl error::Error for Error {
 	fn cause(&self) -> Option<&error::Error> {
 		match *self {
 			Error::Hid(ref e) => Some(e),
 			Error::Usb(ref e) => Some(e),
+			Error::Io(ref e) => Some(e),
 			_ => None,
 		}
 	}
@@ -60,6 +74,7 @@ impl error::Error for Error {
 		match *self {
 			Error::Hid(ref e) => error::Error::description(e),
 			Error::Usb(ref e) => error::Error::description(e),
+			Error::Io(ref e) => error::Error::description(e),
 			Error::DeviceNotFound => "the device to connect to was not found",
 			Error::DeviceDisconnected => "the device is no longer available",
 			Error::UnknownHidVersion => "HID version of the device unknown",
@@ -67,12 +82,14 @@ impl error::Error for Error {
 				"the device produced a data chunk of unexpected size"
 			}
 			Error::DeviceReadTimeout => "timeout expired while reading from device",
+			Error::DeviceWriteTimeout => "timeout expired while writing to device",
 			Error::DeviceBadMagic => "the device sent chunk with wrong magic value",
 			Error::DeviceBadSessionId => "the device sent a message with a wrong session id",
 			Error::DeviceUnexpectedSequenceNumber => {
 				"the device sent an unexpected sequence number"
 			}
 			Error::InvalidMessageType(_) => "received a non-existing message type from the device",
+			Error::MessageTooLarge(_) => "the device announced a message that is too large",
 			Error::NoDeviceSerial => "unable to determine device serial number",
 		}
 	}
@@ -84,8 +101,10 @@ impl fmt::Display for Error {
 		match *self {
 			Error::Hid(ref e) => fmt::Display::fmt(e, f),
 			Error::Usb(ref e) => fmt::Display::fmt(e, f),
+			Error::Io(ref e) => fmt::Display::fmt(e, f),
 			Error::UnexpectedChunkSizeFromDevice(s) => write!(f, "{}: {}", desc(self), s),
 			Error::InvalidMessageType(ref t) => write!(f, "{}: {}", desc(self), t),
+			Error::MessageTooLarge(s) => write!(f, "{}: {} bytes", desc(self), s),
 			_ => f.write_str(desc(self)),
 		}
 	}
@@ -0,0 +1,104 @@
+use std::thread;
+use std::time::Duration;
+
+use testing::{ChunkDirection, Fault, FaultPlan};
+use transport::error::Error;
+use transport::protocol::Link;
+
+/// A link that injects faults in the chunks of another link according to a fault plan.
+pub struct FaultyLink<L: Link> {
+	inner: L,
+	plan: FaultPlan,
+	reads: usize,
+	writes: usize,
+	disconnected: bool,
+}
+
+impl<L: Link> FaultyLink<L> {
+	/// Wrap the link to inject the faults of the plan.
+	pub fn new(link: L, plan: FaultPlan) -> FaultyLink<L> {
+		FaultyLink {
+			inner: link,
+			plan: plan,
+			reads: 0,
+			writes: 0,
+			disconnected: false,
+		}
+	}
+
+	/// Get the fault for the next chunk in the given direction and count the chunk.
+	fn next_fault(&mut self, direction: ChunkDirection) -> Option<Fault> {
+		let counter = match direction {
+			ChunkDirection::Read => &mut self.reads,
+			ChunkDirection::Write => &mut self.writes,
+		};
+		let fault = self.plan.fault(direction, *counter);
+		*counter += 1;
+		if let Some(fault) = fault {
+			debug!("Injecting fault in {:?} chunk #{}: {:?}", direction, *counter - 1, fault);
+		}
+		fault
+	}
+
+	/// Apply the fault to a read chunk.  Returns None if the chunk is dropped.
+	fn apply_read_fault(&mut self, mut chunk: Vec<u8>) -> Result<Option<Vec<u8>>, Error> {
+		match self.next_fault(ChunkDirection::Read) {
+			None => Ok(Some(chunk)),
+			Some(Fault::DropChunk) => Ok(None),
+			Some(Fault::CorruptMagic) => {
+				chunk[0] ^= 0xff;
+				Ok(Some(chunk))
+			}
+			Some(Fault::Delay(delay)) => {
+				thread::sleep(delay);
+				Ok(Some(chunk))
+			}
+			Some(Fault::Disconnect) => {
+				self.disconnected = true;
+				Err(Error::DeviceDisconnected)
+			}
+		}
+	}
+}
+
+impl<L: Link> Link for FaultyLink<L> {
+	fn write_chunk(&mut self, mut chunk: Vec<u8>) -> Result<(), Error> {
+		if self.disconnected {
+			return Err(Error::DeviceDisconnected);
+		}
+		match self.next_fault(ChunkDirection::Write) {
+			None => {}
+			Some(Fault::DropChunk) => return Ok(()),
+			Some(Fault::CorruptMagic) => chunk[0] ^= 0xff,
+			Some(Fault::Delay(delay)) => thread::sleep(delay),
+			Some(Fault::Disconnect) => {
+				self.disconnected = true;
+				return Err(Error::DeviceDisconnected);
+			}
+		}
+		self.inner.write_chunk(chunk)
+	}
+
+	fn read_chunk(&mut self) -> Result<Vec<u8>, Error> {
+		loop {
+			if self.disconnected {
+				return Err(Error::DeviceDisconnected);
+			}
+			let chunk = self.inner.read_chunk()?;
+			if let Some(chunk) = self.apply_read_fault(chunk)? {
+				return Ok(chunk);
+			}
+		}
+	}
+
+	fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+		if self.disconnected {
+			return Err(Error::DeviceDisconnected);
+		}
+		match self.inner.read_chunk_timeout(timeout)? {
+			// A dropped chunk looks like no chunk arrived.
+			Some(chunk) => self.apply_read_fault(chunk),
+			None => Ok(None),
+		}
+	}
+}
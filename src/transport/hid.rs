@@ -1,12 +1,15 @@
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use hid;
 
-use super::super::AvailableDevice;
+use super::super::{AvailableDevice, ConnectOptions};
 use transport::error::Error;
-use transport::protocol::{Link, Protocol, ProtocolV1};
-use transport::{derive_model, AvailableDeviceTransport, ProtoMessage, Transport};
+use transport::protocol::{Link, Protocol, ProtocolV1, DEFAULT_MAX_MESSAGE_SIZE};
+use transport::{
+	derive_model, device_link, AvailableDeviceTransport, DeviceLink, Faults, ProtoMessage,
+	Transport,
+};
 
 mod constants {
 	///! A collection of constants related to the HID protocol.
@@ -24,15 +27,30 @@ const CHUNK_SIZE: usize = 64;
 /// The read timeout.
 const READ_TIMEOUT_MS: u64 = 100000;
 
-/// There are two different HID link protocol versions.
-#[derive(Debug)]
-enum HidVersion {
+/// There are two different HID link protocol versions.  Older Trezor 1 firmware uses V1 report
+/// framing; newer firmware expects an extra report ID byte in front of every chunk (V2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HidVersion {
 	V1,
 	V2,
 }
 
+impl HidVersion {
+	/// Frame a chunk as a HID report.  V2 reports start with the report ID.
+	fn report(&self, chunk: Vec<u8>) -> Vec<u8> {
+		match *self {
+			HidVersion::V1 => chunk,
+			HidVersion::V2 => {
+				let mut report = vec![0];
+				report.extend(chunk);
+				report
+			}
+		}
+	}
+}
+
 /// An available transport for connecting with a device.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AvailableHidTransport {
 	pub serial_nb: String,
 }
@@ -60,30 +78,21 @@ impl Drop for HidLink {
 impl Link for HidLink {
 	fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), Error> {
 		debug_assert_eq!(CHUNK_SIZE, chunk.len());
-		let payload = match self.hid_version {
-			HidVersion::V1 => chunk,
-			HidVersion::V2 => {
-				let mut payload = vec![0];
-				payload.extend(chunk);
-				payload
-			}
-		};
-		self.handle.as_mut().unwrap().data().write(payload)?;
+		let report = self.hid_version.report(chunk);
+		self.handle.as_mut().unwrap().data().write(report)?;
 		Ok(())
 	}
 
 	fn read_chunk(&mut self) -> Result<Vec<u8>, Error> {
+		let timeout = Duration::from_millis(READ_TIMEOUT_MS);
+		self.read_chunk_timeout(timeout)?.ok_or(Error::DeviceReadTimeout)
+	}
+
+	fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
 		let mut chunk = vec![0; 64];
-		//TODO(stevenroose) have different timeouts for messages that do user input
-		match self
-			.handle
-			.as_mut()
-			.unwrap()
-			.data()
-			.read(&mut chunk, Duration::from_millis(READ_TIMEOUT_MS))?
-		{
-			Some(64) => Ok(chunk),
-			None => Err(Error::DeviceReadTimeout),
+		match self.handle.as_mut().unwrap().data().read(&mut chunk, timeout)? {
+			Some(64) => Ok(Some(chunk)),
+			None => Ok(None),
 			Some(chunk_size) => Err(Error::UnexpectedChunkSizeFromDevice(chunk_size)),
 		}
 	}
@@ -105,17 +114,21 @@ fn derive_debug(dev: &hid::Device) -> Option<bool> {
 	}
 }
 
-/// Probe the HID version for a Trezor 1 device.
-fn probe_hid_version(handle: &mut hid::Handle) -> Result<HidVersion, Error> {
+/// Probe the HID version for a Trezor 1 device.  The write function writes a report to the device
+/// and returns the number of bytes written.
+fn probe_hid_version<W>(mut write: W) -> Result<HidVersion, Error>
+where
+	W: FnMut(Vec<u8>) -> Result<usize, Error>,
+{
 	let mut w = vec![0xff; 65];
 	w[0] = 0;
 	w[1] = 63;
-	if handle.data().write(w)? == 65 {
+	if write(w)? == 65 {
 		return Ok(HidVersion::V2);
 	}
 	let mut w = vec![0xff; 64];
 	w[0] = 63;
-	if handle.data().write(w)? == 64 {
+	if write(w)? == 64 {
 		return Ok(HidVersion::V1);
 	}
 	Err(Error::UnknownHidVersion)
@@ -123,7 +136,8 @@ fn probe_hid_version(handle: &mut hid::Handle) -> Result<HidVersion, Error> {
 
 /// An implementation of the Transport interface for HID devices.
 pub struct HidTransport {
-	protocol: ProtocolV1<HidLink>,
+	protocol: ProtocolV1<DeviceLink<HidLink>>,
+	hid_version: HidVersion,
 }
 
 impl HidTransport {
@@ -156,8 +170,13 @@ impl HidTransport {
 		Ok(devices)
 	}
 
-	/// Connect to a device over the HID transport.
-	pub fn connect(device: &AvailableDevice) -> Result<Box<Transport>, Error> {
+	/// Connect to a device over the HID transport.  The HID version is probed unless it is given
+	/// in the options.
+	pub fn connect(
+		device: &AvailableDevice,
+		options: &ConnectOptions,
+		faults: Faults,
+	) -> Result<Box<Transport>, Error> {
 		let transport = match device.transport {
 			AvailableDeviceTransport::Hid(ref t) => t,
 			_ => panic!("passed wrong AvailableDevice in HidTransport::connect"),
@@ -181,15 +200,24 @@ impl HidTransport {
 			})
 			.ok_or(Error::DeviceNotFound)??;
 
-		let hid_version = probe_hid_version(&mut handle)?;
+		let hid_version = match options.hid_version {
+			Some(v) => {
+				debug!("Using HID version {:?} instead of probing", v);
+				v
+			}
+			None => probe_hid_version(|report| Ok(handle.data().write(report)?))?,
+		};
+		let link = HidLink {
+			_hid_manager: hidman,
+			hid_version: hid_version,
+			handle: Some(handle),
+		};
 		Ok(Box::new(HidTransport {
 			protocol: ProtocolV1 {
-				link: HidLink {
-					_hid_manager: hidman,
-					hid_version: hid_version,
-					handle: Some(handle),
-				},
+				link: device_link(link, faults),
+				max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
 			},
+			hid_version: hid_version,
 		}))
 	}
 }
@@ -205,7 +233,172 @@ impl super::Transport for HidTransport {
 	fn write_message(&mut self, message: ProtoMessage) -> Result<(), Error> {
 		self.protocol.write(message)
 	}
+	fn write_message_until(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Instant,
+	) -> Result<(), Error> {
+		self.protocol.write_until(message, deadline)
+	}
 	fn read_message(&mut self) -> Result<ProtoMessage, Error> {
 		self.protocol.read()
 	}
+	fn poll_message(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		self.protocol.poll(timeout, deadline)
+	}
+
+	fn set_max_message_size(&mut self, size: usize) {
+		self.protocol.max_message_size = size;
+	}
+
+	fn hid_version(&self) -> Option<HidVersion> {
+		Some(self.hid_version)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use protos::MessageType;
+	use transport::scripted::ScriptedLink;
+
+	/// A scripted link that frames chunks as HID reports like `HidLink`.
+	struct ScriptedHidLink {
+		hid_version: HidVersion,
+		link: ScriptedLink,
+	}
+
+	impl Link for ScriptedHidLink {
+		fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), Error> {
+			self.link.write_chunk(self.hid_version.report(chunk))
+		}
+
+		fn read_chunk(&mut self) -> Result<Vec<u8>, Error> {
+			self.link.read_chunk()
+		}
+
+		fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+			self.link.read_chunk_timeout(timeout)
+		}
+	}
+
+	fn protocol(hid_version: HidVersion, link: &ScriptedLink) -> ProtocolV1<ScriptedHidLink> {
+		ProtocolV1 {
+			link: ScriptedHidLink {
+				hid_version: hid_version,
+				link: link.clone(),
+			},
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+		}
+	}
+
+	/// A payload that doesn't fit in a single chunk.
+	fn payload() -> Vec<u8> {
+		(0..100).collect()
+	}
+
+	/// The chunks in which the device sends the message.
+	fn device_chunks(message: ProtoMessage) -> Vec<Vec<u8>> {
+		let link = ScriptedLink::new();
+		protocol(HidVersion::V1, &link).write(message).unwrap();
+		link.take_writes()
+	}
+
+	fn assert_bad_magic(result: Result<ProtoMessage, Error>) {
+		match result {
+			Err(Error::DeviceBadMagic) => {}
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("read a message with bad magic"),
+		}
+	}
+
+	#[test]
+	fn write_v1() {
+		let link = ScriptedLink::new();
+		let message = ProtoMessage(MessageType::MessageType_Ping, payload());
+		protocol(HidVersion::V1, &link).write(message).unwrap();
+
+		let reports = link.take_writes();
+		assert_eq!(reports.len(), 2);
+		assert_eq!(reports[0].len(), 64);
+		assert_eq!(&reports[0][0..9], &[0x3f, 0x23, 0x23, 0, 1, 0, 0, 0, 100]);
+		assert_eq!(&reports[0][9..], &payload()[0..55]);
+		assert_eq!(reports[1].len(), 64);
+		assert_eq!(reports[1][0], 0x3f);
+		assert_eq!(&reports[1][1..46], &payload()[55..]);
+		assert!(reports[1][46..].iter().all(|b| *b == 0));
+	}
+
+	#[test]
+	fn write_v2() {
+		let link = ScriptedLink::new();
+		let message = ProtoMessage(MessageType::MessageType_Ping, payload());
+		protocol(HidVersion::V2, &link).write(message).unwrap();
+
+		let reports = link.take_writes();
+		let chunks = device_chunks(ProtoMessage(MessageType::MessageType_Ping, payload()));
+		assert_eq!(reports.len(), 2);
+		for (report, chunk) in reports.iter().zip(chunks.iter()) {
+			assert_eq!(report.len(), 65);
+			assert_eq!(report[0], 0);
+			assert_eq!(&report[1..], &chunk[..]);
+		}
+	}
+
+	#[test]
+	fn read_multi_chunk() {
+		for hid_version in &[HidVersion::V1, HidVersion::V2] {
+			let link = ScriptedLink::new();
+			for chunk in device_chunks(ProtoMessage(MessageType::MessageType_Success, payload())) {
+				link.push_read(chunk);
+			}
+
+			let message = protocol(*hid_version, &link).read().unwrap();
+			assert_eq!(message.message_type(), MessageType::MessageType_Success);
+			assert_eq!(message.payload(), &payload()[..]);
+		}
+	}
+
+	#[test]
+	fn read_bad_magic() {
+		let link = ScriptedLink::new();
+		let mut chunks = device_chunks(ProtoMessage(MessageType::MessageType_Success, payload()));
+		chunks[0][1] = 0x24;
+		for chunk in chunks {
+			link.push_read(chunk);
+		}
+		assert_bad_magic(protocol(HidVersion::V1, &link).read());
+	}
+
+	#[test]
+	fn read_bad_continuation_magic() {
+		let link = ScriptedLink::new();
+		let mut chunks = device_chunks(ProtoMessage(MessageType::MessageType_Success, payload()));
+		chunks[1][0] = 0x00;
+		for chunk in chunks {
+			link.push_read(chunk);
+		}
+		assert_bad_magic(protocol(HidVersion::V2, &link).read());
+	}
+
+	#[test]
+	fn probe_version() {
+		assert_eq!(probe_hid_version(|report| Ok(report.len())).unwrap(), HidVersion::V2);
+		let v1 = probe_hid_version(|report| {
+			Ok(if report.len() == 65 {
+				0
+			} else {
+				report.len()
+			})
+		});
+		assert_eq!(v1.unwrap(), HidVersion::V1);
+		match probe_hid_version(|_| Ok(0)) {
+			Err(Error::UnknownHidVersion) => {}
+			r => panic!("unexpected probe result: {:?}", r.map_err(|e| e.to_string())),
+		}
+	}
 }
@@ -1,20 +1,49 @@
+use std::time::{Duration, Instant};
+
 use fmt;
 use protobuf;
 
-use super::{AvailableDevice, Model};
+use super::{AvailableDevice, ConnectOptions, Model};
 use protos::MessageType;
 
 pub mod error;
+#[cfg(any(test, feature = "testing"))]
+pub mod faulty;
 pub mod hid;
 pub mod protocol;
+#[cfg(any(test, feature = "testing"))]
+pub mod scripted;
+pub mod udp;
 pub mod webusb;
 
 /// An available transport for a Trezor device, containing any of the different supported
 /// transports.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AvailableDeviceTransport {
 	Hid(hid::AvailableHidTransport),
 	WebUsb(webusb::AvailableWebUsbTransport),
+	Udp(udp::AvailableUdpTransport),
+}
+
+impl AvailableDeviceTransport {
+	/// A short name of the kind of transport.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			AvailableDeviceTransport::Hid(_) => "hid",
+			AvailableDeviceTransport::WebUsb(_) => "webusb",
+			AvailableDeviceTransport::Udp(_) => "udp",
+		}
+	}
+
+	/// An identifier of the device on the transport: the serial number for HID, the bus and
+	/// address for WebUSB and the host and port for UDP.
+	pub fn id(&self) -> String {
+		match self {
+			AvailableDeviceTransport::Hid(ref t) => t.serial_nb.clone(),
+			AvailableDeviceTransport::WebUsb(ref t) => format!("{}:{}", t.bus, t.address),
+			AvailableDeviceTransport::Udp(ref t) => format!("{}:{}", t.host, t.port),
+		}
+	}
 }
 
 impl fmt::Display for AvailableDeviceTransport {
@@ -22,6 +51,7 @@ impl fmt::Display for AvailableDeviceTransport {
 		match self {
 			AvailableDeviceTransport::Hid(ref t) => write!(f, "{}", t),
 			AvailableDeviceTransport::WebUsb(ref t) => write!(f, "{}", t),
+			AvailableDeviceTransport::Udp(ref t) => write!(f, "{}", t),
 		}
 	}
 }
@@ -48,6 +78,16 @@ impl ProtoMessage {
 	pub fn into_message<M: protobuf::Message>(self) -> Result<M, protobuf::error::ProtobufError> {
 		Ok(protobuf::parse_from_bytes(&self.into_payload())?)
 	}
+
+	/// Take the payload from the ProtoMessage and parse it to a protobuf message without
+	/// enforcing the required fields.
+	pub fn into_message_lenient<M: protobuf::Message>(
+		self,
+	) -> Result<M, protobuf::error::ProtobufError> {
+		let mut msg = M::new();
+		msg.merge_from_bytes(&self.into_payload())?;
+		Ok(msg)
+	}
 }
 
 /// The transport interface that is implemented by the different ways to communicate with a Trezor
@@ -57,15 +97,80 @@ pub trait Transport {
 	fn session_end(&mut self) -> Result<(), error::Error>;
 
 	fn write_message(&mut self, message: ProtoMessage) -> Result<(), error::Error>;
+	/// Write a message, or fail with `Error::DeviceWriteTimeout` if the deadline passes before it
+	/// is completely written.
+	fn write_message_until(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Instant,
+	) -> Result<(), error::Error>;
 	fn read_message(&mut self) -> Result<ProtoMessage, error::Error>;
+	/// Read a message, or return None if the device didn't start sending one within the timeout.
+	/// This is used to wait for messages that depend on user interaction.  If a deadline is
+	/// given, the rest of the message must be received before it, otherwise
+	/// `Error::DeviceReadTimeout` is returned.
+	fn poll_message(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, error::Error>;
+
+	/// Set the maximum size of messages accepted from the device.
+	fn set_max_message_size(&mut self, size: usize);
+
+	/// The HID version used for the connection, if it's a HID transport.
+	fn hid_version(&self) -> Option<hid::HidVersion> {
+		None
+	}
+}
+
+/// The link of a device connection.  With the `testing` feature, faults can be injected in it.
+#[cfg(any(test, feature = "testing"))]
+type DeviceLink<L> = faulty::FaultyLink<L>;
+
+/// The link of a device connection.  With the `testing` feature, faults can be injected in it.
+#[cfg(not(any(test, feature = "testing")))]
+type DeviceLink<L> = L;
+
+/// The faults to inject in the link of a device connection.
+#[cfg(any(test, feature = "testing"))]
+pub type Faults = ::testing::FaultPlan;
+
+/// The faults to inject in the link of a device connection.  Without the `testing` feature, no
+/// faults can be injected.
+#[cfg(not(any(test, feature = "testing")))]
+#[derive(Debug, Clone, Default)]
+pub struct Faults;
+
+/// Wrap the link to inject the faults.
+#[cfg(any(test, feature = "testing"))]
+fn device_link<L: protocol::Link>(link: L, faults: Faults) -> DeviceLink<L> {
+	faulty::FaultyLink::new(link, faults)
+}
+
+/// Wrap the link to inject the faults.
+#[cfg(not(any(test, feature = "testing")))]
+fn device_link<L: protocol::Link>(link: L, _faults: Faults) -> DeviceLink<L> {
+	link
 }
 
 /// A delegation method to connect an available device transport.  It delegates to the different
 /// transport types.
-pub fn connect(available_device: &AvailableDevice) -> Result<Box<Transport>, error::Error> {
+pub fn connect(
+	available_device: &AvailableDevice,
+	options: &ConnectOptions,
+	faults: Faults,
+) -> Result<Box<Transport>, error::Error> {
 	match available_device.transport {
-		AvailableDeviceTransport::Hid(_) => hid::HidTransport::connect(&available_device),
-		AvailableDeviceTransport::WebUsb(_) => webusb::WebUsbTransport::connect(&available_device),
+		AvailableDeviceTransport::Hid(_) => {
+			hid::HidTransport::connect(&available_device, options, faults)
+		}
+		AvailableDeviceTransport::WebUsb(_) => {
+			webusb::WebUsbTransport::connect(&available_device, options, faults)
+		}
+		AvailableDeviceTransport::Udp(_) => {
+			udp::UdpTransport::connect(&available_device, options, faults)
+		}
 	}
 }
 
@@ -1,4 +1,5 @@
 use std::cmp;
+use std::time::{Duration, Instant};
 
 use byteorder::{BigEndian, ByteOrder};
 use protobuf::ProtobufEnum;
@@ -11,6 +12,8 @@ use transport::ProtoMessage;
 pub trait Link {
 	fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), Error>;
 	fn read_chunk(&mut self) -> Result<Vec<u8>, Error>;
+	/// Read a chunk, or return None if no chunk arrived within the timeout.
+	fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error>;
 }
 
 /// A protocol is used to encode messages in chunks that can be sent to the device and to parse
@@ -19,18 +22,55 @@ pub trait Protocol {
 	fn session_begin(&mut self) -> Result<(), Error>;
 	fn session_end(&mut self) -> Result<(), Error>;
 	fn write(&mut self, message: ProtoMessage) -> Result<(), Error>;
+	/// Write a message, or fail with `Error::DeviceWriteTimeout` if the deadline passes before
+	/// all chunks are written.
+	fn write_until(&mut self, message: ProtoMessage, deadline: Instant) -> Result<(), Error>;
 	fn read(&mut self) -> Result<ProtoMessage, Error>;
+	/// Read a message, or return None if the device didn't start sending one within the timeout.
+	/// If a deadline is given, the rest of the message must be received before it, otherwise
+	/// `Error::DeviceReadTimeout` is returned.
+	fn poll(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error>;
+}
+
+/// Read the next chunk of a message, before the deadline if one is given.
+fn read_next_chunk<L: Link>(link: &mut L, deadline: Option<Instant>) -> Result<Vec<u8>, Error> {
+	let deadline = match deadline {
+		Some(deadline) => deadline,
+		None => return link.read_chunk(),
+	};
+	let now = Instant::now();
+	if now >= deadline {
+		return Err(Error::DeviceReadTimeout);
+	}
+	link.read_chunk_timeout(deadline - now)?.ok_or(Error::DeviceReadTimeout)
+}
+
+/// Check that the deadline for writing a message, if any, didn't pass.
+fn check_write_deadline(deadline: Option<Instant>) -> Result<(), Error> {
+	match deadline {
+		Some(deadline) if Instant::now() >= deadline => Err(Error::DeviceWriteTimeout),
+		_ => Ok(()),
+	}
 }
 
 /// The length of the chunks sent.
 const REPLEN: usize = 64;
 
+/// The default maximum size of a message received from the device.  Messages sent by the device
+/// are small, so this leaves plenty of room while protecting against excessive allocations.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
 /// V2 of the binary protocol.
 /// This version is currently not in use by any device and is subject to change.
 #[allow(dead_code)]
 pub struct ProtocolV2<L: Link> {
 	pub link: L,
 	pub session_id: u32,
+	pub max_message_size: usize,
 }
 
 impl<L: Link> Protocol for ProtocolV2<L> {
@@ -63,6 +103,37 @@ impl<L: Link> Protocol for ProtocolV2<L> {
 	}
 
 	fn write(&mut self, message: ProtoMessage) -> Result<(), Error> {
+		self.write_chunks(message, None)
+	}
+
+	fn write_until(&mut self, message: ProtoMessage, deadline: Instant) -> Result<(), Error> {
+		self.write_chunks(message, Some(deadline))
+	}
+
+	fn read(&mut self) -> Result<ProtoMessage, Error> {
+		let chunk = self.link.read_chunk()?;
+		self.read_from(chunk, None)
+	}
+
+	fn poll(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		match self.link.read_chunk_timeout(timeout)? {
+			Some(chunk) => Ok(Some(self.read_from(chunk, deadline)?)),
+			None => Ok(None),
+		}
+	}
+}
+
+impl<L: Link> ProtocolV2<L> {
+	/// Write the message in chunks, before the deadline if one is given.
+	fn write_chunks(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Option<Instant>,
+	) -> Result<(), Error> {
 		assert!(self.session_id != 0);
 
 		// First generate the total payload, then write it to the transport in chunks.
@@ -96,16 +167,22 @@ impl<L: Link> Protocol for ProtocolV2<L> {
 			debug_assert!(chunk.len() <= REPLEN);
 			chunk.resize(REPLEN, 0);
 
+			check_write_deadline(deadline)?;
 			self.link.write_chunk(chunk)?;
 		}
 
 		Ok(())
 	}
 
-	fn read(&mut self) -> Result<ProtoMessage, Error> {
+	/// Read the rest of the message that starts with the given chunk, before the deadline if one
+	/// is given.
+	fn read_from(
+		&mut self,
+		chunk: Vec<u8>,
+		deadline: Option<Instant>,
+	) -> Result<ProtoMessage, Error> {
 		debug_assert!(self.session_id != 0);
 
-		let chunk = self.link.read_chunk()?;
 		if chunk[0] != 0x01 {
 			println!("bad magic in v2 read: {:x} instead of 0x01", chunk[0]);
 			return Err(Error::DeviceBadMagic);
@@ -117,11 +194,20 @@ impl<L: Link> Protocol for ProtocolV2<L> {
 		let message_type = MessageType::from_i32(message_type_id as i32)
 			.ok_or(Error::InvalidMessageType(message_type_id))?;
 		let data_length = BigEndian::read_u32(&chunk[9..13]) as usize;
+		if data_length > self.max_message_size {
+			// Discard the rest of the message without buffering it, so that the next message can
+			// still be read.
+			let remaining = data_length.saturating_sub(chunk.len() - 13);
+			if let Err(e) = self.discard(remaining, deadline) {
+				debug!("Failed to discard the rest of a message that is too large: {}", e);
+			}
+			return Err(Error::MessageTooLarge(data_length));
+		}
 
 		let mut data: Vec<u8> = chunk[13..].into();
 		let mut seq = 0;
 		while data.len() < data_length {
-			let chunk = self.link.read_chunk()?;
+			let chunk = read_next_chunk(&mut self.link, deadline)?;
 			if chunk[0] != 0x02 {
 				println!("bad magic in v2 session_begin: {:x} instead of 0x02", chunk[0]);
 				return Err(Error::DeviceBadMagic);
@@ -139,11 +225,24 @@ impl<L: Link> Protocol for ProtocolV2<L> {
 
 		Ok(ProtoMessage(message_type, data[0..data_length].into()))
 	}
+
+	/// Read and discard the continuation chunks carrying the given number of payload bytes.
+	fn discard(&mut self, mut remaining: usize, deadline: Option<Instant>) -> Result<(), Error> {
+		while remaining > 0 {
+			let chunk = read_next_chunk(&mut self.link, deadline)?;
+			if chunk[0] != 0x02 {
+				return Err(Error::DeviceBadMagic);
+			}
+			remaining = remaining.saturating_sub(chunk.len() - 9);
+		}
+		Ok(())
+	}
 }
 
 /// The original binary protocol.
 pub struct ProtocolV1<L: Link> {
 	pub link: L,
+	pub max_message_size: usize,
 }
 
 impl<L: Link> Protocol for ProtocolV1<L> {
@@ -156,6 +255,37 @@ impl<L: Link> Protocol for ProtocolV1<L> {
 	}
 
 	fn write(&mut self, message: ProtoMessage) -> Result<(), Error> {
+		self.write_chunks(message, None)
+	}
+
+	fn write_until(&mut self, message: ProtoMessage, deadline: Instant) -> Result<(), Error> {
+		self.write_chunks(message, Some(deadline))
+	}
+
+	fn read(&mut self) -> Result<ProtoMessage, Error> {
+		let chunk = self.link.read_chunk()?;
+		self.read_from(chunk, None)
+	}
+
+	fn poll(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		match self.link.read_chunk_timeout(timeout)? {
+			Some(chunk) => Ok(Some(self.read_from(chunk, deadline)?)),
+			None => Ok(None),
+		}
+	}
+}
+
+impl<L: Link> ProtocolV1<L> {
+	/// Write the message in chunks, before the deadline if one is given.
+	fn write_chunks(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Option<Instant>,
+	) -> Result<(), Error> {
 		// First generate the total payload, then write it to the transport in chunks.
 		let mut data = vec![0; 8];
 		data[0] = 0x23;
@@ -173,14 +303,20 @@ impl<L: Link> Protocol for ProtocolV1<L> {
 			debug_assert!(chunk.len() <= REPLEN);
 			chunk.resize(REPLEN, 0);
 
+			check_write_deadline(deadline)?;
 			self.link.write_chunk(chunk)?;
 		}
 
 		Ok(())
 	}
 
-	fn read(&mut self) -> Result<ProtoMessage, Error> {
-		let chunk = self.link.read_chunk()?;
+	/// Read the rest of the message that starts with the given chunk, before the deadline if one
+	/// is given.
+	fn read_from(
+		&mut self,
+		chunk: Vec<u8>,
+		deadline: Option<Instant>,
+	) -> Result<ProtoMessage, Error> {
 		if chunk[0] != 0x3f || chunk[1] != 0x23 || chunk[2] != 0x23 {
 			println!(
 				"bad magic in v1 read: {:x}{:x}{:x} instead of 0x3f2323",
@@ -192,10 +328,19 @@ impl<L: Link> Protocol for ProtocolV1<L> {
 		let message_type = MessageType::from_i32(message_type_id as i32)
 			.ok_or(Error::InvalidMessageType(message_type_id))?;
 		let data_length = BigEndian::read_u32(&chunk[5..9]) as usize;
+		if data_length > self.max_message_size {
+			// Discard the rest of the message without buffering it, so that the next message can
+			// still be read.
+			let remaining = data_length.saturating_sub(chunk.len() - 9);
+			if let Err(e) = self.discard(remaining, deadline) {
+				debug!("Failed to discard the rest of a message that is too large: {}", e);
+			}
+			return Err(Error::MessageTooLarge(data_length));
+		}
 		let mut data: Vec<u8> = chunk[9..].into();
 
 		while data.len() < data_length {
-			let chunk = self.link.read_chunk()?;
+			let chunk = read_next_chunk(&mut self.link, deadline)?;
 			if chunk[0] != 0x3f {
 				println!("bad magic in v1 read: {:x} instead of 0x3f", chunk[0]);
 				return Err(Error::DeviceBadMagic);
@@ -206,4 +351,92 @@ impl<L: Link> Protocol for ProtocolV1<L> {
 
 		Ok(ProtoMessage(message_type, data[0..data_length].into()))
 	}
+
+	/// Read and discard the continuation chunks carrying the given number of payload bytes.
+	fn discard(&mut self, mut remaining: usize, deadline: Option<Instant>) -> Result<(), Error> {
+		while remaining > 0 {
+			let chunk = read_next_chunk(&mut self.link, deadline)?;
+			if chunk[0] != 0x3f {
+				return Err(Error::DeviceBadMagic);
+			}
+			remaining = remaining.saturating_sub(chunk.len() - 1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use messages::TrezorMessage;
+	use protobuf::Message;
+	use protos;
+	use transport::scripted::ScriptedLink;
+
+	fn protocol(link: &ScriptedLink) -> ProtocolV1<ScriptedLink> {
+		ProtocolV1 {
+			link: link.clone(),
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+		}
+	}
+
+	/// A message that takes more than one chunk.
+	fn long_success() -> protos::Success {
+		let mut success = protos::Success::new();
+		success.set_message("a".repeat(100));
+		success
+	}
+
+	fn proto_message<M: TrezorMessage>(message: &M) -> ProtoMessage {
+		ProtoMessage(M::message_type(), message.write_to_bytes().unwrap())
+	}
+
+	#[test]
+	fn poll_before_deadline() {
+		let link = ScriptedLink::new();
+		link.push_message(&long_success());
+		let deadline = Instant::now() + Duration::from_secs(60);
+		let msg = protocol(&link).poll(Duration::from_secs(1), Some(deadline)).unwrap().unwrap();
+		assert_eq!(msg.payload(), &long_success().write_to_bytes().unwrap()[..]);
+	}
+
+	#[test]
+	fn poll_after_deadline() {
+		let link = ScriptedLink::new();
+		link.push_message(&long_success());
+		// The first chunk is read within the timeout, but the deadline also bounds the rest.
+		match protocol(&link).poll(Duration::from_secs(1), Some(Instant::now())) {
+			Err(Error::DeviceReadTimeout) => {}
+			r => panic!("unexpected result: {:?}", r.map(|m| m.map(|m| m.message_type()))),
+		}
+	}
+
+	#[test]
+	fn message_too_large() {
+		let link = ScriptedLink::new();
+		link.push_message(&long_success());
+		link.push_message(&protos::Success::new());
+		let mut protocol = protocol(&link);
+		protocol.max_message_size = 10;
+		match protocol.read() {
+			Err(Error::MessageTooLarge(size)) => {
+				assert_eq!(size, long_success().write_to_bytes().unwrap().len())
+			}
+			r => panic!("unexpected result: {:?}", r.map(|m| m.message_type())),
+		}
+		// The rest of the large message is discarded, so the next message can be read.
+		let msg = protocol.read().unwrap();
+		assert_eq!(msg.message_type(), MessageType::MessageType_Success);
+		assert!(msg.payload().is_empty());
+	}
+
+	#[test]
+	fn write_after_deadline() {
+		let link = ScriptedLink::new();
+		match protocol(&link).write_until(proto_message(&long_success()), Instant::now()) {
+			Err(Error::DeviceWriteTimeout) => {}
+			r => panic!("unexpected result: {:?}", r),
+		}
+		assert!(link.take_writes().is_empty());
+	}
 }
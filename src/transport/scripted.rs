@@ -0,0 +1,127 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use messages::TrezorMessage;
+use testing::FaultPlan;
+use transport::error::Error;
+use transport::faulty::FaultyLink;
+use transport::protocol::{Link, Protocol, ProtocolV1, DEFAULT_MAX_MESSAGE_SIZE};
+use transport::{ProtoMessage, Transport};
+
+/// The chunks of a scripted link.
+#[derive(Debug, Default)]
+struct Script {
+	reads: VecDeque<Vec<u8>>,
+	writes: Vec<Vec<u8>>,
+}
+
+/// An in-memory link that returns scripted chunks when reading and records the written chunks.
+/// Clones share the same script, so the script can be extended and inspected after the link is
+/// passed to a protocol.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedLink {
+	script: Rc<RefCell<Script>>,
+}
+
+impl ScriptedLink {
+	/// Create a link without chunks to read.
+	pub fn new() -> ScriptedLink {
+		ScriptedLink::default()
+	}
+
+	/// Add a chunk to be read from the link.
+	pub fn push_read(&self, chunk: Vec<u8>) {
+		self.script.borrow_mut().reads.push_back(chunk);
+	}
+
+	/// Add the chunks of a message sent by the device, framed in the original protocol.
+	pub fn push_message<M: TrezorMessage>(&self, message: &M) {
+		let payload = message.write_to_bytes().expect("scripted message misses required fields");
+		let encoder = ScriptedLink::new();
+		let mut protocol = ProtocolV1 {
+			link: encoder.clone(),
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+		};
+		protocol
+			.write(ProtoMessage(M::message_type(), payload))
+			.expect("writing to a scripted link can't fail");
+		for chunk in encoder.take_writes() {
+			self.push_read(chunk);
+		}
+	}
+
+	/// Take the chunks written to the link so far.
+	pub fn take_writes(&self) -> Vec<Vec<u8>> {
+		let mut script = self.script.borrow_mut();
+		script.writes.drain(..).collect()
+	}
+}
+
+impl Link for ScriptedLink {
+	fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), Error> {
+		self.script.borrow_mut().writes.push(chunk);
+		Ok(())
+	}
+
+	fn read_chunk(&mut self) -> Result<Vec<u8>, Error> {
+		self.script.borrow_mut().reads.pop_front().ok_or(Error::DeviceReadTimeout)
+	}
+
+	fn read_chunk_timeout(&mut self, _timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+		Ok(self.script.borrow_mut().reads.pop_front())
+	}
+}
+
+/// A transport over a scripted link, to exercise the client without a device.  The link uses the
+/// original protocol and the faults of the plan are injected in it.
+pub struct ScriptedTransport {
+	protocol: ProtocolV1<FaultyLink<ScriptedLink>>,
+}
+
+impl ScriptedTransport {
+	/// Create a transport over the link.
+	pub fn new(link: ScriptedLink, faults: FaultPlan) -> ScriptedTransport {
+		ScriptedTransport {
+			protocol: ProtocolV1 {
+				link: FaultyLink::new(link, faults),
+				max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			},
+		}
+	}
+}
+
+impl Transport for ScriptedTransport {
+	fn session_begin(&mut self) -> Result<(), Error> {
+		self.protocol.session_begin()
+	}
+	fn session_end(&mut self) -> Result<(), Error> {
+		self.protocol.session_end()
+	}
+
+	fn write_message(&mut self, message: ProtoMessage) -> Result<(), Error> {
+		self.protocol.write(message)
+	}
+	fn write_message_until(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Instant,
+	) -> Result<(), Error> {
+		self.protocol.write_until(message, deadline)
+	}
+	fn read_message(&mut self) -> Result<ProtoMessage, Error> {
+		self.protocol.read()
+	}
+	fn poll_message(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		self.protocol.poll(timeout, deadline)
+	}
+
+	fn set_max_message_size(&mut self, size: usize) {
+		self.protocol.max_message_size = size;
+	}
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::io;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use super::super::{AvailableDevice, ConnectOptions, Model};
+use transport::error::Error;
+use transport::protocol::{Link, Protocol, ProtocolV1, DEFAULT_MAX_MESSAGE_SIZE};
+use transport::{
+	device_link, AvailableDeviceTransport, DeviceLink, Faults, ProtoMessage, Transport,
+};
+
+/// The chunk size for the serial protocol.
+const CHUNK_SIZE: usize = 64;
+
+const READ_TIMEOUT_MS: u64 = 100000;
+const PING_TIMEOUT_MS: u64 = 500;
+
+/// An available transport for connecting with an emulator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvailableUdpTransport {
+	pub host: String,
+	pub port: u16,
+}
+
+impl fmt::Display for AvailableUdpTransport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "UDP ({}:{})", self.host, self.port)
+	}
+}
+
+/// A UDP link to an emulator over which bytes can be sent.
+pub struct UdpLink {
+	socket: UdpSocket,
+}
+
+impl UdpLink {
+	/// Open a socket to the emulator at the given host and port.
+	fn open(host: &str, port: u16) -> Result<UdpLink, Error> {
+		let socket = UdpSocket::bind("0.0.0.0:0")?;
+		socket.connect((host, port))?;
+		Ok(UdpLink {
+			socket: socket,
+		})
+	}
+
+	/// Check if the emulator is responding.
+	fn ping(&mut self) -> Result<bool, Error> {
+		self.socket.send(b"PINGPING")?;
+		let mut resp = [0; CHUNK_SIZE];
+		self.socket.set_read_timeout(Some(Duration::from_millis(PING_TIMEOUT_MS)))?;
+		match self.socket.recv(&mut resp) {
+			Ok(n) => Ok(&resp[..n] == b"PONGPONG"),
+			Err(ref e) if is_timeout(e) => Ok(false),
+			// There is nobody listening on the port.
+			Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(false),
+			Err(e) => Err(e.into()),
+		}
+	}
+}
+
+/// Check if the IO error is a timeout.  Platforms differ in the kind they use.
+fn is_timeout(e: &io::Error) -> bool {
+	e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+impl Link for UdpLink {
+	fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), Error> {
+		debug_assert_eq!(CHUNK_SIZE, chunk.len());
+		self.socket.send(&chunk)?;
+		Ok(())
+	}
+
+	fn read_chunk(&mut self) -> Result<Vec<u8>, Error> {
+		let timeout = Duration::from_millis(READ_TIMEOUT_MS);
+		self.read_chunk_timeout(timeout)?.ok_or(Error::DeviceReadTimeout)
+	}
+
+	fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+		let mut chunk = vec![0; CHUNK_SIZE];
+		self.socket.set_read_timeout(Some(timeout))?;
+		match self.socket.recv(&mut chunk) {
+			Ok(n) if n == CHUNK_SIZE => Ok(Some(chunk)),
+			Ok(n) => Err(Error::UnexpectedChunkSizeFromDevice(n)),
+			Err(ref e) if is_timeout(e) => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+}
+
+/// An implementation of the Transport interface for the emulator over UDP.
+pub struct UdpTransport {
+	protocol: ProtocolV1<DeviceLink<UdpLink>>,
+}
+
+impl UdpTransport {
+	/// Find the emulator listening at the given host and port.  The emulator doesn't report its
+	/// model before it's initialized, so it's assumed to be the Trezor 2 emulator.
+	pub fn find_devices(host: &str, port: u16, debug: bool) -> Result<Vec<AvailableDevice>, Error> {
+		// The debug link listens on the next port, so there is none for the last port.
+		let port = if debug {
+			match port.checked_add(1) {
+				Some(port) => port,
+				None => return Ok(Vec::new()),
+			}
+		} else {
+			port
+		};
+		if !UdpLink::open(host, port)?.ping()? {
+			return Ok(Vec::new());
+		}
+		Ok(vec![AvailableDevice {
+			model: Model::Trezor2,
+			debug: debug,
+			transport: AvailableDeviceTransport::Udp(AvailableUdpTransport {
+				host: host.to_owned(),
+				port: port,
+			}),
+		}])
+	}
+
+	/// Connect to an emulator over the UDP transport.
+	pub fn connect(
+		device: &AvailableDevice,
+		_options: &ConnectOptions,
+		faults: Faults,
+	) -> Result<Box<Transport>, Error> {
+		let transport = match device.transport {
+			AvailableDeviceTransport::Udp(ref t) => t,
+			_ => panic!("passed wrong AvailableDevice in UdpTransport::connect"),
+		};
+
+		let mut link = UdpLink::open(&transport.host, transport.port)?;
+		if !link.ping()? {
+			return Err(Error::DeviceDisconnected);
+		}
+		Ok(Box::new(UdpTransport {
+			protocol: ProtocolV1 {
+				link: device_link(link, faults),
+				max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			},
+		}))
+	}
+}
+
+impl super::Transport for UdpTransport {
+	fn session_begin(&mut self) -> Result<(), Error> {
+		self.protocol.session_begin()
+	}
+	fn session_end(&mut self) -> Result<(), Error> {
+		self.protocol.session_end()
+	}
+
+	fn write_message(&mut self, message: ProtoMessage) -> Result<(), Error> {
+		self.protocol.write(message)
+	}
+	fn write_message_until(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Instant,
+	) -> Result<(), Error> {
+		self.protocol.write_until(message, deadline)
+	}
+	fn read_message(&mut self) -> Result<ProtoMessage, Error> {
+		self.protocol.read()
+	}
+	fn poll_message(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		self.protocol.poll(timeout, deadline)
+	}
+
+	fn set_max_message_size(&mut self, size: usize) {
+		self.protocol.max_message_size = size;
+	}
+}
@@ -1,12 +1,15 @@
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use libusb;
 
-use super::super::AvailableDevice;
+use super::super::{AvailableDevice, ConnectOptions};
 use transport::error::Error;
-use transport::protocol::{Link, Protocol, ProtocolV1};
-use transport::{derive_model, AvailableDeviceTransport, ProtoMessage, Transport};
+use transport::protocol::{Link, Protocol, ProtocolV1, DEFAULT_MAX_MESSAGE_SIZE};
+use transport::{
+	derive_model, device_link, AvailableDeviceTransport, DeviceLink, Faults, ProtoMessage,
+	Transport,
+};
 
 mod constants {
 	///! A collection of constants related to the WebUsb protocol.
@@ -30,7 +33,7 @@ const READ_TIMEOUT_MS: u64 = 100000;
 const WRITE_TIMEOUT_MS: u64 = 100000;
 
 /// An available transport for connecting with a device.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AvailableWebUsbTransport {
 	pub bus: u8,
 	pub address: u8,
@@ -80,11 +83,23 @@ impl Link for WebUsbLink {
 			Err(Error::DeviceReadTimeout)
 		}
 	}
+
+	fn read_chunk_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+		let mut chunk = vec![0; CHUNK_SIZE];
+		let endpoint = constants::READ_ENDPOINT_MASK | self.endpoint;
+
+		match self.handle.read_interrupt(endpoint, &mut chunk, timeout) {
+			Ok(n) if n == CHUNK_SIZE => Ok(Some(chunk)),
+			Ok(_) => Err(Error::DeviceReadTimeout),
+			Err(libusb::Error::Timeout) => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
 }
 
 /// An implementation of the Transport interface for WebUSB devices.
 pub struct WebUsbTransport {
-	protocol: ProtocolV1<WebUsbLink>,
+	protocol: ProtocolV1<DeviceLink<WebUsbLink>>,
 }
 
 impl WebUsbTransport {
@@ -128,7 +143,11 @@ impl WebUsbTransport {
 	}
 
 	/// Connect to a device over the WebUSB transport.
-	pub fn connect(device: &AvailableDevice) -> Result<Box<Transport>, Error> {
+	pub fn connect(
+		device: &AvailableDevice,
+		options: &ConnectOptions,
+		faults: Faults,
+	) -> Result<Box<Transport>, Error> {
 		let transport = match device.transport {
 			AvailableDeviceTransport::WebUsb(ref t) => t,
 			_ => panic!("passed wrong AvailableDevice in WebUsbTransport::connect"),
@@ -167,16 +186,18 @@ impl WebUsbTransport {
 		let handle_ptr = Box::into_raw(Box::new(handle));
 		let handle_ref = unsafe { &mut *handle_ptr as &'static mut libusb::DeviceHandle<'static> };
 
+		let link = WebUsbLink {
+			libusb_context: context_ref,
+			handle: handle_ref,
+			endpoint: match device.debug {
+				false => constants::ENDPOINT,
+				true => constants::ENDPOINT_DEBUG,
+			},
+		};
 		Ok(Box::new(WebUsbTransport {
 			protocol: ProtocolV1 {
-				link: WebUsbLink {
-					libusb_context: context_ref,
-					handle: handle_ref,
-					endpoint: match device.debug {
-						false => constants::ENDPOINT,
-						true => constants::ENDPOINT_DEBUG,
-					},
-				},
+				link: device_link(link, faults),
+				max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
 			},
 		}))
 	}
@@ -193,7 +214,25 @@ impl super::Transport for WebUsbTransport {
 	fn write_message(&mut self, message: ProtoMessage) -> Result<(), Error> {
 		self.protocol.write(message)
 	}
+	fn write_message_until(
+		&mut self,
+		message: ProtoMessage,
+		deadline: Instant,
+	) -> Result<(), Error> {
+		self.protocol.write_until(message, deadline)
+	}
 	fn read_message(&mut self) -> Result<ProtoMessage, Error> {
 		self.protocol.read()
 	}
+	fn poll_message(
+		&mut self,
+		timeout: Duration,
+		deadline: Option<Instant>,
+	) -> Result<Option<ProtoMessage>, Error> {
+		self.protocol.poll(timeout, deadline)
+	}
+
+	fn set_max_message_size(&mut self, size: usize) {
+		self.protocol.max_message_size = size;
+	}
 }
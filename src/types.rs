@@ -0,0 +1,728 @@
+//! # Public types
+//!
+//! Crate-owned types used in the public interface of this library.  They wrap the raw protobuf
+//! messages from the `protos` module so that changes to the generated protobuf code don't
+//! propagate to users of this library.
+
+use std::fmt;
+
+use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
+use bitcoin_hashes::sha256d;
+use protobuf::{Message, ProtobufEnum};
+
+use error::{Error, Result};
+use protos;
+use utils;
+use wordlist::Language;
+
+/// The field number of `capabilities` in the Features message of newer firmware.
+const FEATURES_CAPABILITIES: u32 = 30;
+
+/// The type of a message exchanged with the device, f.e. as reported in
+/// `Error::UnexpectedMessageType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageKind(protos::MessageType);
+
+impl MessageKind {
+	/// The number identifying the message type on the wire.
+	pub fn number(&self) -> u32 {
+		self.0.value() as u32
+	}
+}
+
+impl fmt::Display for MessageKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = format!("{:?}", self.0);
+		f.write_str(name.trim_start_matches("MessageType_"))
+	}
+}
+
+impl From<protos::MessageType> for MessageKind {
+	fn from(t: protos::MessageType) -> MessageKind {
+		MessageKind(t)
+	}
+}
+
+/// The features reported by the device on initialization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceFeatures {
+	vendor: Option<String>,
+	version: (u32, u32, u32),
+	bootloader_mode: bool,
+	device_id: Option<String>,
+	pin_protection: bool,
+	passphrase_protection: bool,
+	language: Option<String>,
+	label: Option<String>,
+	initialized: bool,
+	revision: Option<Vec<u8>>,
+	bootloader_hash: Option<Vec<u8>>,
+	imported: bool,
+	pin_cached: bool,
+	passphrase_cached: bool,
+	firmware_present: bool,
+	needs_backup: bool,
+	flags: u32,
+	model: Option<String>,
+	firmware_version: Option<(u32, u32, u32)>,
+	firmware_vendor: Option<String>,
+	firmware_vendor_keys: Option<Vec<u8>>,
+	unfinished_backup: bool,
+	no_backup: bool,
+	capabilities: Option<Vec<Capability>>,
+}
+
+impl DeviceFeatures {
+	/// The name of the manufacturer, e.g. "trezor.io".
+	pub fn vendor(&self) -> Option<&str> {
+		self.vendor.as_ref().map(|s| s.as_str())
+	}
+
+	/// The version of the firmware or bootloader as (major, minor, patch).
+	pub fn version(&self) -> (u32, u32, u32) {
+		self.version
+	}
+
+	/// Whether the device is in bootloader mode.
+	pub fn bootloader_mode(&self) -> bool {
+		self.bootloader_mode
+	}
+
+	/// The device's unique identifier.
+	pub fn device_id(&self) -> Option<&str> {
+		self.device_id.as_ref().map(|s| s.as_str())
+	}
+
+	/// Whether the device is protected by a PIN.
+	pub fn pin_protection(&self) -> bool {
+		self.pin_protection
+	}
+
+	/// Whether the seed is protected by a passphrase.
+	pub fn passphrase_protection(&self) -> bool {
+		self.passphrase_protection
+	}
+
+	/// The device language.
+	pub fn language(&self) -> Option<&str> {
+		self.language.as_ref().map(|s| s.as_str())
+	}
+
+	/// The device label.
+	pub fn label(&self) -> Option<&str> {
+		self.label.as_ref().map(|s| s.as_str())
+	}
+
+	/// Whether the device contains a seed.
+	pub fn initialized(&self) -> bool {
+		self.initialized
+	}
+
+	/// The SCM revision of the firmware.
+	pub fn revision(&self) -> Option<&[u8]> {
+		self.revision.as_ref().map(|r| r.as_slice())
+	}
+
+	/// The hash of the bootloader.
+	pub fn bootloader_hash(&self) -> Option<&[u8]> {
+		self.bootloader_hash.as_ref().map(|h| h.as_slice())
+	}
+
+	/// Whether the storage was imported from an external source.
+	pub fn imported(&self) -> bool {
+		self.imported
+	}
+
+	/// Whether the PIN is cached in the current session.
+	pub fn pin_cached(&self) -> bool {
+		self.pin_cached
+	}
+
+	/// Whether the passphrase is cached in the current session.
+	pub fn passphrase_cached(&self) -> bool {
+		self.passphrase_cached
+	}
+
+	/// Whether valid firmware is loaded.
+	pub fn firmware_present(&self) -> bool {
+		self.firmware_present
+	}
+
+	/// Whether the storage needs a backup.
+	pub fn needs_backup(&self) -> bool {
+		self.needs_backup
+	}
+
+	/// The device flags.
+	pub fn flags(&self) -> u32 {
+		self.flags
+	}
+
+	/// The device hardware model.
+	pub fn model(&self) -> Option<&str> {
+		self.model.as_ref().map(|s| s.as_str())
+	}
+
+	/// The version of the installed firmware as (major, minor, patch).  Only reported in
+	/// bootloader mode.
+	pub fn firmware_version(&self) -> Option<(u32, u32, u32)> {
+		self.firmware_version
+	}
+
+	/// The vendor of the installed firmware.  Only reported in bootloader mode.
+	pub fn firmware_vendor(&self) -> Option<&str> {
+		self.firmware_vendor.as_ref().map(|s| s.as_str())
+	}
+
+	/// The hash of the vendor keys of the installed firmware.  Only reported in bootloader mode.
+	pub fn firmware_vendor_keys(&self) -> Option<&[u8]> {
+		self.firmware_vendor_keys.as_ref().map(|k| k.as_slice())
+	}
+
+	/// Whether a backup was started but not finished.
+	pub fn unfinished_backup(&self) -> bool {
+		self.unfinished_backup
+	}
+
+	/// Whether the device was initialized without a backup.
+	pub fn no_backup(&self) -> bool {
+		self.no_backup
+	}
+
+	/// The capabilities reported by the device.  Only reported by firmware 1.9.0 and 2.3.0 or
+	/// newer, use `Trezor::supports()` to also cover older firmware.
+	pub fn capabilities(&self) -> Option<&[Capability]> {
+		self.capabilities.as_ref().map(|c| c.as_slice())
+	}
+}
+
+/// Parse the capabilities from the unknown fields of the Features message.  Unknown capabilities
+/// are skipped.
+fn parse_capabilities(f: &protos::Features) -> Option<Vec<Capability>> {
+	let values = f.get_unknown_fields().get(FEATURES_CAPABILITIES)?;
+	let mut numbers: Vec<u64> = values.varint.clone();
+	// Repeated fields can also be sent packed.
+	for packed in &values.length_delimited {
+		let mut value = 0u64;
+		let mut shift = 0;
+		for byte in packed {
+			if shift < 64 {
+				value |= ((byte & 0x7f) as u64) << shift;
+			}
+			shift += 7;
+			if byte & 0x80 == 0 {
+				numbers.push(value);
+				value = 0;
+				shift = 0;
+			}
+		}
+	}
+	Some(numbers.into_iter().filter_map(|n| Capability::from_number(n as u32)).collect())
+}
+
+impl From<protos::Features> for DeviceFeatures {
+	fn from(mut f: protos::Features) -> DeviceFeatures {
+		DeviceFeatures {
+			vendor: if f.has_vendor() {
+				Some(f.take_vendor())
+			} else {
+				None
+			},
+			version: (f.get_major_version(), f.get_minor_version(), f.get_patch_version()),
+			bootloader_mode: f.get_bootloader_mode(),
+			device_id: if f.has_device_id() {
+				Some(f.take_device_id())
+			} else {
+				None
+			},
+			pin_protection: f.get_pin_protection(),
+			passphrase_protection: f.get_passphrase_protection(),
+			language: if f.has_language() {
+				Some(f.take_language())
+			} else {
+				None
+			},
+			label: if f.has_label() {
+				Some(f.take_label())
+			} else {
+				None
+			},
+			initialized: f.get_initialized(),
+			revision: if f.has_revision() {
+				Some(f.take_revision())
+			} else {
+				None
+			},
+			bootloader_hash: if f.has_bootloader_hash() {
+				Some(f.take_bootloader_hash())
+			} else {
+				None
+			},
+			imported: f.get_imported(),
+			pin_cached: f.get_pin_cached(),
+			passphrase_cached: f.get_passphrase_cached(),
+			firmware_present: f.get_firmware_present(),
+			needs_backup: f.get_needs_backup(),
+			flags: f.get_flags(),
+			model: if f.has_model() {
+				Some(f.take_model())
+			} else {
+				None
+			},
+			firmware_version: if f.has_fw_major() {
+				Some((f.get_fw_major(), f.get_fw_minor(), f.get_fw_patch()))
+			} else {
+				None
+			},
+			firmware_vendor: if f.has_fw_vendor() {
+				Some(f.take_fw_vendor())
+			} else {
+				None
+			},
+			firmware_vendor_keys: if f.has_fw_vendor_keys() {
+				Some(f.take_fw_vendor_keys())
+			} else {
+				None
+			},
+			unfinished_backup: f.get_unfinished_backup(),
+			no_backup: f.get_no_backup(),
+			capabilities: parse_capabilities(&f),
+		}
+	}
+}
+
+/// Information reported by a device in bootloader mode, used to decide which firmware to install.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootloaderInfo {
+	/// The version of the bootloader as (major, minor, patch).
+	pub bootloader_version: (u32, u32, u32),
+	/// The hash of the bootloader.
+	pub bootloader_hash: Option<Vec<u8>>,
+	/// Whether valid firmware is installed.
+	pub firmware_present: bool,
+	/// The version of the installed firmware as (major, minor, patch).
+	pub firmware_version: Option<(u32, u32, u32)>,
+	/// The vendor of the installed firmware.
+	pub firmware_vendor: Option<String>,
+	/// The hash of the vendor keys of the installed firmware.
+	pub firmware_vendor_keys: Option<Vec<u8>>,
+}
+
+impl BootloaderInfo {
+	/// Get the bootloader info from the features.  Returns None if the device is not in
+	/// bootloader mode.
+	pub fn from_features(features: &DeviceFeatures) -> Option<BootloaderInfo> {
+		if !features.bootloader_mode {
+			return None;
+		}
+		Some(BootloaderInfo {
+			bootloader_version: features.version,
+			bootloader_hash: features.bootloader_hash.clone(),
+			firmware_present: features.firmware_present,
+			firmware_version: features.firmware_version,
+			firmware_vendor: features.firmware_vendor.clone(),
+			firmware_vendor_keys: features.firmware_vendor_keys.clone(),
+		})
+	}
+}
+
+/// The different error codes a device can report in a failure message.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum FailureCode {
+	UnexpectedMessage,
+	ButtonExpected,
+	DataError,
+	ActionCancelled,
+	PinExpected,
+	PinCancelled,
+	PinInvalid,
+	InvalidSignature,
+	ProcessError,
+	NotEnoughFunds,
+	NotInitialized,
+	PinMismatch,
+	FirmwareError,
+}
+
+impl From<protos::Failure_FailureType> for FailureCode {
+	fn from(t: protos::Failure_FailureType) -> FailureCode {
+		use protos::Failure_FailureType::*;
+		match t {
+			Failure_UnexpectedMessage => FailureCode::UnexpectedMessage,
+			Failure_ButtonExpected => FailureCode::ButtonExpected,
+			Failure_DataError => FailureCode::DataError,
+			Failure_ActionCancelled => FailureCode::ActionCancelled,
+			Failure_PinExpected => FailureCode::PinExpected,
+			Failure_PinCancelled => FailureCode::PinCancelled,
+			Failure_PinInvalid => FailureCode::PinInvalid,
+			Failure_InvalidSignature => FailureCode::InvalidSignature,
+			Failure_ProcessError => FailureCode::ProcessError,
+			Failure_NotEnoughFunds => FailureCode::NotEnoughFunds,
+			Failure_NotInitialized => FailureCode::NotInitialized,
+			Failure_PinMismatch => FailureCode::PinMismatch,
+			Failure_FirmwareError => FailureCode::FirmwareError,
+		}
+	}
+}
+
+/// A failure message sent by the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+	code: Option<FailureCode>,
+	message: String,
+}
+
+impl Failure {
+	/// The error code of the failure, if the device provided one.
+	pub fn code(&self) -> Option<FailureCode> {
+		self.code
+	}
+
+	/// The human-readable message of the failure.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+}
+
+impl fmt::Display for Failure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, r#"code={:?} message="{}""#, self.code, self.message)
+	}
+}
+
+impl From<protos::Failure> for Failure {
+	fn from(mut f: protos::Failure) -> Failure {
+		Failure {
+			code: if f.has_code() {
+				Some(f.get_code().into())
+			} else {
+				None
+			},
+			message: f.take_message(),
+		}
+	}
+}
+
+/// The different types of button requests the device can send.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum ButtonRequestType {
+	Other,
+	FeeOverThreshold,
+	ConfirmOutput,
+	ResetDevice,
+	ConfirmWord,
+	WipeDevice,
+	ProtectCall,
+	SignTx,
+	FirmwareCheck,
+	Address,
+	PublicKey,
+	MnemonicWordCount,
+	MnemonicInput,
+	PassphraseType,
+	UnknownDerivationPath,
+}
+
+impl From<protos::ButtonRequest_ButtonRequestType> for ButtonRequestType {
+	fn from(t: protos::ButtonRequest_ButtonRequestType) -> ButtonRequestType {
+		use protos::ButtonRequest_ButtonRequestType::*;
+		match t {
+			ButtonRequest_Other => ButtonRequestType::Other,
+			ButtonRequest_FeeOverThreshold => ButtonRequestType::FeeOverThreshold,
+			ButtonRequest_ConfirmOutput => ButtonRequestType::ConfirmOutput,
+			ButtonRequest_ResetDevice => ButtonRequestType::ResetDevice,
+			ButtonRequest_ConfirmWord => ButtonRequestType::ConfirmWord,
+			ButtonRequest_WipeDevice => ButtonRequestType::WipeDevice,
+			ButtonRequest_ProtectCall => ButtonRequestType::ProtectCall,
+			ButtonRequest_SignTx => ButtonRequestType::SignTx,
+			ButtonRequest_FirmwareCheck => ButtonRequestType::FirmwareCheck,
+			ButtonRequest_Address => ButtonRequestType::Address,
+			ButtonRequest_PublicKey => ButtonRequestType::PublicKey,
+			ButtonRequest_MnemonicWordCount => ButtonRequestType::MnemonicWordCount,
+			ButtonRequest_MnemonicInput => ButtonRequestType::MnemonicInput,
+			ButtonRequest_PassphraseType => ButtonRequestType::PassphraseType,
+			ButtonRequest_UnknownDerivationPath => ButtonRequestType::UnknownDerivationPath,
+		}
+	}
+}
+
+/// The different types of PIN matrix requests the device can send.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum PinMatrixRequestType {
+	/// The current PIN.
+	Current,
+	/// The new PIN.
+	NewFirst,
+	/// The new PIN again for confirmation.
+	NewSecond,
+}
+
+impl From<protos::PinMatrixRequest_PinMatrixRequestType> for PinMatrixRequestType {
+	fn from(t: protos::PinMatrixRequest_PinMatrixRequestType) -> PinMatrixRequestType {
+		use protos::PinMatrixRequest_PinMatrixRequestType::*;
+		match t {
+			PinMatrixRequestType_Current => PinMatrixRequestType::Current,
+			PinMatrixRequestType_NewFirst => PinMatrixRequestType::NewFirst,
+			PinMatrixRequestType_NewSecond => PinMatrixRequestType::NewSecond,
+		}
+	}
+}
+
+/// The different script types for inputs and addresses.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Copy)]
+pub enum InputScriptType {
+	/// Standard P2PKH address.
+	SpendAddress,
+	/// P2SH multisig address.
+	SpendMultisig,
+	/// Reserved for external inputs (coinjoin).
+	External,
+	/// Native segwit.
+	SpendWitness,
+	/// Segwit over P2SH (backward compatible).
+	SpendP2shWitness,
+}
+
+impl From<protos::InputScriptType> for InputScriptType {
+	fn from(t: protos::InputScriptType) -> InputScriptType {
+		match t {
+			protos::InputScriptType::SPENDADDRESS => InputScriptType::SpendAddress,
+			protos::InputScriptType::SPENDMULTISIG => InputScriptType::SpendMultisig,
+			protos::InputScriptType::EXTERNAL => InputScriptType::External,
+			protos::InputScriptType::SPENDWITNESS => InputScriptType::SpendWitness,
+			protos::InputScriptType::SPENDP2SHWITNESS => InputScriptType::SpendP2shWitness,
+		}
+	}
+}
+
+impl From<InputScriptType> for protos::InputScriptType {
+	fn from(t: InputScriptType) -> protos::InputScriptType {
+		match t {
+			InputScriptType::SpendAddress => protos::InputScriptType::SPENDADDRESS,
+			InputScriptType::SpendMultisig => protos::InputScriptType::SPENDMULTISIG,
+			InputScriptType::External => protos::InputScriptType::EXTERNAL,
+			InputScriptType::SpendWitness => protos::InputScriptType::SPENDWITNESS,
+			InputScriptType::SpendP2shWitness => protos::InputScriptType::SPENDP2SHWITNESS,
+		}
+	}
+}
+
+/// The type of data the device requests in the transaction signing flow.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum TxRequestType {
+	/// An input of a transaction.
+	Input,
+	/// An output of a transaction.
+	Output,
+	/// The metadata of a transaction, like the version and the number of inputs and outputs.
+	Meta,
+	/// A chunk of the extra data of a transaction.
+	ExtraData,
+	/// The signing process is finished.
+	Finished,
+}
+
+impl From<protos::TxRequest_RequestType> for TxRequestType {
+	fn from(t: protos::TxRequest_RequestType) -> TxRequestType {
+		match t {
+			protos::TxRequest_RequestType::TXINPUT => TxRequestType::Input,
+			protos::TxRequest_RequestType::TXOUTPUT => TxRequestType::Output,
+			protos::TxRequest_RequestType::TXMETA => TxRequestType::Meta,
+			protos::TxRequest_RequestType::TXEXTRADATA => TxRequestType::ExtraData,
+			protos::TxRequest_RequestType::TXFINISHED => TxRequestType::Finished,
+		}
+	}
+}
+
+/// The details of a request of the device in the transaction signing flow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRequestInfo {
+	/// The type of data requested.
+	pub request_type: TxRequestType,
+	/// The index of the requested input or output.
+	pub request_index: Option<usize>,
+	/// The TXID of the previous transaction the data is requested from.  None if the data is
+	/// requested from the transaction being signed.
+	pub tx_hash: Option<sha256d::Hash>,
+	/// The offset of the requested chunk of extra data.
+	pub extra_data_offset: Option<usize>,
+	/// The length of the requested chunk of extra data.
+	pub extra_data_len: Option<usize>,
+}
+
+impl TxRequestInfo {
+	pub(crate) fn from_request(req: &protos::TxRequest) -> Result<TxRequestInfo> {
+		let details = req.get_details();
+		let tx_hash = if details.has_tx_hash() {
+			Some(
+				utils::from_rev_bytes(details.get_tx_hash())
+					.ok_or(Error::MalformedTxRequest("invalid tx hash".to_owned()))?,
+			)
+		} else {
+			None
+		};
+		Ok(TxRequestInfo {
+			request_type: req.get_request_type().into(),
+			request_index: if details.has_request_index() {
+				Some(details.get_request_index() as usize)
+			} else {
+				None
+			},
+			tx_hash: tx_hash,
+			extra_data_offset: if details.has_extra_data_offset() {
+				Some(details.get_extra_data_offset() as usize)
+			} else {
+				None
+			},
+			extra_data_len: if details.has_extra_data_len() {
+				Some(details.get_extra_data_len() as usize)
+			} else {
+				None
+			},
+		})
+	}
+}
+
+impl fmt::Display for TxRequestInfo {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.request_type {
+			TxRequestType::Input => write!(f, "input")?,
+			TxRequestType::Output => write!(f, "output")?,
+			TxRequestType::Meta => write!(f, "metadata")?,
+			TxRequestType::ExtraData => write!(f, "extra data")?,
+			TxRequestType::Finished => return write!(f, "finished"),
+		}
+		if let Some(idx) = self.request_index {
+			write!(f, " {}", idx)?;
+		}
+		match self.tx_hash {
+			Some(ref txid) => write!(f, " of previous tx {}", txid),
+			None => write!(f, " of signed tx"),
+		}
+	}
+}
+
+/// Where the user should enter the passphrase.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum PassphraseMode {
+	/// The passphrase is entered on the host.
+	Host,
+	/// The passphrase is entered on the device.
+	Device,
+	/// The device asks the user each time.  Newer firmware doesn't have this option and lets the
+	/// host decide on each request, so it behaves like `Host` there.
+	Ask,
+}
+
+impl From<PassphraseMode> for protos::ApplySettings_PassphraseSourceType {
+	fn from(m: PassphraseMode) -> protos::ApplySettings_PassphraseSourceType {
+		match m {
+			PassphraseMode::Host => protos::ApplySettings_PassphraseSourceType::HOST,
+			PassphraseMode::Device => protos::ApplySettings_PassphraseSourceType::DEVICE,
+			PassphraseMode::Ask => protos::ApplySettings_PassphraseSourceType::ASK,
+		}
+	}
+}
+
+/// The different types of Bitcoin addresses.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Copy)]
+pub enum AddressType {
+	/// Pay to pubkey hash.
+	P2pkh,
+	/// Pay to script hash.
+	P2sh,
+	/// Pay to witness pubkey hash.
+	P2wpkh,
+	/// Pay to witness script hash.
+	P2wsh,
+}
+
+/// The chains supported by `Trezor::get_address_any()`.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum Chain {
+	/// Bitcoin or a Bitcoin testnet with the given script type.
+	Bitcoin(Network, InputScriptType),
+	/// Ethereum and other EVM chains.
+	Ethereum,
+	/// NEM on the given network: 0x68 for mainnet, 0x98 for testnet and 0x60 for Mijin.
+	Nem(u8),
+	/// Ripple.
+	Ripple,
+	/// Stellar.
+	Stellar,
+}
+
+/// Options for getting an address with `Trezor::get_address_any()`.
+#[derive(PartialEq, Eq, Clone, Debug, Copy, Default)]
+pub struct AddressOptions {
+	/// Show the address on the device so the user can verify it.
+	pub show_display: bool,
+	/// Show the address split into chunks for easier verification.  Only supported for
+	/// Ethereum and ignored for the other chains.
+	pub chunkify: bool,
+}
+
+/// Options for the mnemonic of a seed recovered with `Trezor::recover_device_with_options()` or
+/// created with `Trezor::reset_device_with_options()`.
+#[derive(PartialEq, Eq, Clone, Debug, Copy, Default)]
+pub struct MnemonicOptions {
+	/// The language of the mnemonic, which must be supported by the firmware.  Defaults to
+	/// English.
+	pub language: Language,
+}
+
+/// The features a device can support, as queried with `Trezor::supports()`.  Most of them are
+/// reported by the device, `Taproot` is derived from the firmware version.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Copy)]
+pub enum Capability {
+	Bitcoin,
+	/// Bitcoin-like coins such as Litecoin and Zcash.
+	BitcoinLike,
+	Binance,
+	Cardano,
+	/// Cipher keys and other generic cryptography.
+	Crypto,
+	Eos,
+	Ethereum,
+	Lisk,
+	Monero,
+	Nem,
+	Ripple,
+	Stellar,
+	Tezos,
+	U2f,
+	/// Shamir backups (SLIP-39).
+	Shamir,
+	/// Shamir backups with groups.
+	ShamirGroups,
+	/// Entering the passphrase on the device.
+	PassphraseEntry,
+	/// Signing Taproot inputs.  Only describes the firmware, this crate can't sign them yet.
+	Taproot,
+}
+
+impl Capability {
+	/// Get the capability for the number used in the Features message.
+	fn from_number(number: u32) -> Option<Capability> {
+		Some(match number {
+			1 => Capability::Bitcoin,
+			2 => Capability::BitcoinLike,
+			3 => Capability::Binance,
+			4 => Capability::Cardano,
+			5 => Capability::Crypto,
+			6 => Capability::Eos,
+			7 => Capability::Ethereum,
+			8 => Capability::Lisk,
+			9 => Capability::Monero,
+			10 => Capability::Nem,
+			11 => Capability::Ripple,
+			12 => Capability::Stellar,
+			13 => Capability::Tezos,
+			14 => Capability::U2f,
+			15 => Capability::Shamir,
+			16 => Capability::ShamirGroups,
+			17 => Capability::PassphraseEntry,
+			_ => return None,
+		})
+	}
+}
@@ -1,11 +1,12 @@
 use bitcoin::blockdata::script::Script;
 use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
-use bitcoin::util::{address, bip32, psbt};
+use bitcoin::util::{address, base58, bip32, psbt};
 use bitcoin_bech32::{u5, WitnessProgram};
 use bitcoin_hashes::{hash160, sha256d, Hash};
 use secp256k1;
 
 use error::{Error, Result};
+use types::{AddressType, InputScriptType};
 
 /// convert Network to bech32 network (this should go away soon)
 fn bech_network(network: Network) -> bitcoin_bech32::constants::Network {
@@ -59,6 +60,27 @@ pub fn psbt_find_input(
 	psbt.inputs.get(idx).ok_or(Error::TxRequestInvalidIndex(idx))
 }
 
+/// Get the amount of the output spent by the PSBT input, if the PSBT has the UTXO.
+pub fn psbt_input_amount(psbt: &psbt::PartiallySignedTransaction, index: usize) -> Option<u64> {
+	let input = psbt.inputs.get(index)?;
+	match input.witness_utxo {
+		Some(ref txout) => Some(txout.value),
+		None => {
+			let vout = psbt.global.unsigned_tx.input.get(index)?.previous_output.vout;
+			input.non_witness_utxo.as_ref()?.output.get(vout as usize).map(|o| o.value)
+		}
+	}
+}
+
+/// Get the derivation path of the key signing the PSBT input, if the PSBT has exactly one.
+pub fn psbt_input_path(psbt: &psbt::PartiallySignedTransaction, index: usize) -> Option<Vec<u32>> {
+	psbt.inputs
+		.get(index)
+		.filter(|i| i.hd_keypaths.len() == 1)
+		.and_then(|i| i.hd_keypaths.values().next())
+		.map(|v| convert_path(&v.1))
+}
+
 /// Get a hash from a reverse byte representation.
 pub fn from_rev_bytes(rev_bytes: &[u8]) -> Option<sha256d::Hash> {
 	let mut bytes = rev_bytes.to_vec();
@@ -100,7 +122,368 @@ pub fn coin_name(network: Network) -> Result<String> {
 	}
 }
 
+/// Encode a string as a JSON string literal.
+pub(crate) fn json_string(s: &str) -> String {
+	let mut ret = String::with_capacity(s.len() + 2);
+	ret.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => ret.push_str("\\\""),
+			'\\' => ret.push_str("\\\\"),
+			'\n' => ret.push_str("\\n"),
+			'\r' => ret.push_str("\\r"),
+			'\t' => ret.push_str("\\t"),
+			c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+			c => ret.push(c),
+		}
+	}
+	ret.push('"');
+	ret
+}
+
 /// Convert a BIP-32 derivation path into a Vec<u32>.
 pub fn convert_path(path: &bip32::DerivationPath) -> Vec<u32> {
 	path.into_iter().map(|i| u32::from(*i)).collect()
 }
+
+/// Format a derivation path like "m/44'/0'/0'".
+pub fn path_to_string(path: &[u32]) -> String {
+	let mut s = "m".to_owned();
+	for i in path {
+		if *i & 0x80000000 != 0 {
+			s.push_str(&format!("/{}'", *i & 0x7fffffff));
+		} else {
+			s.push_str(&format!("/{}", i));
+		}
+	}
+	s
+}
+
+/// The BIP-32 hardened derivation flag.
+pub const HARDENED: u32 = 0x80000000;
+
+/// Get the script type corresponding to a BIP-43 purpose: 44 for legacy, 49 for nested segwit and
+/// 84 for native segwit.  Other purposes, like 86 for taproot, are not supported.
+pub fn script_type_from_purpose(purpose: u32) -> Option<InputScriptType> {
+	match purpose {
+		44 => Some(InputScriptType::SpendAddress),
+		49 => Some(InputScriptType::SpendP2shWitness),
+		84 => Some(InputScriptType::SpendWitness),
+		_ => None,
+	}
+}
+
+/// Get the BIP-43 purpose for single-key accounts of the given script type.
+pub fn purpose_from_script_type(script_type: InputScriptType) -> Option<u32> {
+	match script_type {
+		InputScriptType::SpendAddress => Some(44),
+		InputScriptType::SpendP2shWitness => Some(49),
+		InputScriptType::SpendWitness => Some(84),
+		InputScriptType::SpendMultisig | InputScriptType::External => None,
+	}
+}
+
+/// Get the script type from the purpose of a BIP-43 derivation path.
+pub fn script_type_from_path(path: &[u32]) -> Option<InputScriptType> {
+	match path.first() {
+		Some(p) if *p & HARDENED != 0 => script_type_from_purpose(*p & !HARDENED),
+		_ => None,
+	}
+}
+
+/// Get the type of addresses produced by the given script type.
+pub fn address_type_from_script_type(script_type: InputScriptType) -> Option<AddressType> {
+	match script_type {
+		InputScriptType::SpendAddress => Some(AddressType::P2pkh),
+		InputScriptType::SpendMultisig => Some(AddressType::P2sh),
+		InputScriptType::SpendP2shWitness => Some(AddressType::P2sh),
+		InputScriptType::SpendWitness => Some(AddressType::P2wpkh),
+		InputScriptType::External => None,
+	}
+}
+
+/// Get the type of the given address.
+pub fn address_type(address: &address::Address) -> Option<AddressType> {
+	match address.payload {
+		address::Payload::PubkeyHash(_) => Some(AddressType::P2pkh),
+		address::Payload::ScriptHash(_) => Some(AddressType::P2sh),
+		address::Payload::WitnessProgram(ref prog) => {
+			if prog.version().to_u8() != 0 {
+				return None;
+			}
+			match prog.program().len() {
+				20 => Some(AddressType::P2wpkh),
+				32 => Some(AddressType::P2wsh),
+				_ => None,
+			}
+		}
+	}
+}
+
+/// Encodes the output scripts of a Bitcoin-family coin as addresses.  Coins that hash or encode
+/// their addresses differently from Bitcoin implement this to be used in the signing flow.
+pub trait AddressCodec {
+	/// The name of the coin as used by the device.
+	fn coin_name(&self) -> Result<String>;
+
+	/// Get the address of the output script, or None if it has no address.
+	fn address_from_script(&self, script: &Script) -> Option<String>;
+}
+
+/// The address codec for Bitcoin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitcoinCodec {
+	pub network: Network,
+}
+
+impl AddressCodec for BitcoinCodec {
+	fn coin_name(&self) -> Result<String> {
+		coin_name(self.network)
+	}
+
+	fn address_from_script(&self, script: &Script) -> Option<String> {
+		address_from_script(script, self.network).map(|a| a.to_string())
+	}
+}
+
+/// Encode a segwit program as a bech32 address with the given human-readable part.  The program
+/// is validated with `WitnessProgram`, but that only knows the human-readable parts of a few
+/// coins, so the address is encoded with the underlying bech32 library.
+#[cfg(feature = "groestlcoin")]
+fn bech32_segwit_address(hrp: &str, version: u8, program: &[u8]) -> Option<String> {
+	use bech32::{Bech32, ToBase32};
+
+	let version = u5::try_from_u8(version).ok()?;
+	// The network only determines the human-readable part.
+	let network = bitcoin_bech32::constants::Network::Bitcoin;
+	WitnessProgram::new(version, program.to_vec(), network).ok()?;
+	let mut data = vec![version];
+	data.extend(program.to_base32());
+	Bech32::new(hrp.to_owned(), data).ok().map(|b| b.to_string())
+}
+
+/// The address codec for Groestlcoin.  Groestlcoin uses Groestl-512 instead of double SHA-256
+/// for the Base58 checksum and its own address versions.
+#[cfg(feature = "groestlcoin")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroestlcoinCodec {
+	pub testnet: bool,
+}
+
+#[cfg(feature = "groestlcoin")]
+impl AddressCodec for GroestlcoinCodec {
+	fn coin_name(&self) -> Result<String> {
+		Ok(if self.testnet {
+			"Groestlcoin Testnet"
+		} else {
+			"Groestlcoin"
+		}
+		.to_owned())
+	}
+
+	fn address_from_script(&self, script: &Script) -> Option<String> {
+		use groestl::{Digest, Groestl512};
+
+		let base58 = |version: u8, hash: &[u8]| {
+			let mut data = vec![version];
+			data.extend_from_slice(hash);
+			let checksum = Groestl512::digest(&Groestl512::digest(&data));
+			data.extend_from_slice(&checksum[0..4]);
+			base58::encode_slice(&data)
+		};
+		let bytes = script.as_bytes();
+		if script.is_p2pkh() {
+			Some(base58(
+				if self.testnet {
+					111
+				} else {
+					36
+				},
+				&bytes[3..23],
+			))
+		} else if script.is_p2sh() {
+			Some(base58(
+				if self.testnet {
+					196
+				} else {
+					5
+				},
+				&bytes[2..22],
+			))
+		} else if script.is_v0_p2wpkh() || script.is_v0_p2wsh() {
+			let hrp = if self.testnet {
+				"tgrs"
+			} else {
+				"grs"
+			};
+			bech32_segwit_address(hrp, 0, &bytes[2..])
+		} else {
+			None
+		}
+	}
+}
+
+/// The address codec for Decred.  Decred uses two-byte address versions and BLAKE-256 instead
+/// of double SHA-256 for the Base58 checksum.  It has no segwit addresses.
+#[cfg(feature = "decred")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecredCodec {
+	pub testnet: bool,
+}
+
+#[cfg(feature = "decred")]
+impl AddressCodec for DecredCodec {
+	fn coin_name(&self) -> Result<String> {
+		Ok(if self.testnet {
+			"Decred Testnet"
+		} else {
+			"Decred"
+		}
+		.to_owned())
+	}
+
+	fn address_from_script(&self, script: &Script) -> Option<String> {
+		use blake_hash::{Blake256, Digest};
+
+		let base58 = |version: [u8; 2], hash: &[u8]| {
+			let mut data = version.to_vec();
+			data.extend_from_slice(hash);
+			let checksum = Blake256::digest(&Blake256::digest(&data));
+			data.extend_from_slice(&checksum[0..4]);
+			base58::encode_slice(&data)
+		};
+		let bytes = script.as_bytes();
+		if script.is_p2pkh() {
+			let version = if self.testnet {
+				[0x0f, 0x21]
+			} else {
+				[0x07, 0x3f]
+			};
+			Some(base58(version, &bytes[3..23]))
+		} else if script.is_p2sh() {
+			let version = if self.testnet {
+				[0x0e, 0xfc]
+			} else {
+				[0x07, 0x1a]
+			};
+			Some(base58(version, &bytes[2..22]))
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hex;
+
+	fn script(hex_script: &str) -> Script {
+		Script::from(hex::decode(hex_script).unwrap())
+	}
+
+	const SINGLE_KEY_SCRIPT_TYPES: [InputScriptType; 3] = [
+		InputScriptType::SpendAddress,
+		InputScriptType::SpendP2shWitness,
+		InputScriptType::SpendWitness,
+	];
+
+	#[test]
+	fn purpose_round_trip() {
+		for &script_type in SINGLE_KEY_SCRIPT_TYPES.iter() {
+			let purpose = purpose_from_script_type(script_type).unwrap();
+			assert_eq!(script_type_from_purpose(purpose), Some(script_type));
+		}
+		assert_eq!(purpose_from_script_type(InputScriptType::SpendWitness), Some(84));
+		assert_eq!(purpose_from_script_type(InputScriptType::SpendMultisig), None);
+		assert_eq!(purpose_from_script_type(InputScriptType::External), None);
+		assert_eq!(script_type_from_purpose(86), None);
+	}
+
+	#[test]
+	fn script_type_of_path() {
+		let path = [49 | HARDENED, HARDENED, HARDENED, 0, 0];
+		assert_eq!(script_type_from_path(&path), Some(InputScriptType::SpendP2shWitness));
+		assert_eq!(script_type_from_path(&[84 | HARDENED]), Some(InputScriptType::SpendWitness));
+		// The purpose must be hardened.
+		assert_eq!(script_type_from_path(&[84, HARDENED, HARDENED]), None);
+		assert_eq!(script_type_from_path(&[86 | HARDENED, HARDENED, HARDENED]), None);
+		assert_eq!(script_type_from_path(&[]), None);
+	}
+
+	#[test]
+	fn address_types() {
+		let scripts = [
+			("76a91498af0aaca388a7e1024f505c033626d908e3b54a88ac", AddressType::P2pkh),
+			("a9147557920fbc32a1ef4ef26bae5e8ce3f95abf09ce87", AddressType::P2sh),
+			("00147557920fbc32a1ef4ef26bae5e8ce3f95abf09ce", AddressType::P2wpkh),
+			(
+				"00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+				AddressType::P2wsh,
+			),
+		];
+		for &(hex_script, expected) in scripts.iter() {
+			let address = address_from_script(&script(hex_script), Network::Bitcoin).unwrap();
+			assert_eq!(address_type(&address), Some(expected));
+		}
+
+		assert_eq!(
+			address_type_from_script_type(InputScriptType::SpendWitness),
+			Some(AddressType::P2wpkh)
+		);
+		assert_eq!(
+			address_type_from_script_type(InputScriptType::SpendP2shWitness),
+			Some(AddressType::P2sh)
+		);
+		assert_eq!(address_type_from_script_type(InputScriptType::External), None);
+	}
+
+	#[cfg(feature = "groestlcoin")]
+	#[test]
+	fn groestlcoin_addresses() {
+		let mainnet = GroestlcoinCodec {
+			testnet: false,
+		};
+		let p2pkh = script("76a91498af0aaca388a7e1024f505c033626d908e3b54a88ac");
+		assert_eq!(
+			mainnet.address_from_script(&p2pkh).unwrap(),
+			"Fj62rBJi8LvbmWu2jzkaUX1NFXLEqDLoZM"
+		);
+		let p2wpkh = script("00147557920fbc32a1ef4ef26bae5e8ce3f95abf09ce");
+		assert_eq!(
+			mainnet.address_from_script(&p2wpkh).unwrap(),
+			"grs1qw4teyraux2s77nhjdwh9ar8rl9dt7zww8r6lne"
+		);
+
+		let testnet = GroestlcoinCodec {
+			testnet: true,
+		};
+		let p2wpkh = script("0014b31dc2a236505a6cb9201fa0411ca38a254a7bf1");
+		assert_eq!(
+			testnet.address_from_script(&p2wpkh).unwrap(),
+			"tgrs1qkvwu9g3k2pdxewfqr7syz89r3gj557l3ued7ja"
+		);
+	}
+
+	#[cfg(feature = "decred")]
+	#[test]
+	fn decred_addresses() {
+		let mainnet = DecredCodec {
+			testnet: false,
+		};
+		let p2pkh = script("76a9142789d58cfa0957d206f025c2af056fc8a77cebb088ac");
+		assert_eq!(
+			mainnet.address_from_script(&p2pkh).unwrap(),
+			"DsUZxxoHJSty8DCfwfartwTYbuhmVct7tJu"
+		);
+
+		let testnet = DecredCodec {
+			testnet: true,
+		};
+		let p2pkh = script("76a9140438f8efe52e42253a6417600f01b4a80e3c934388ac");
+		assert_eq!(
+			testnet.address_from_script(&p2pkh).unwrap(),
+			"TsRQTRqf5TdEfqsnJ1gcQEDvPP363cEjr4B"
+		);
+	}
+}
@@ -0,0 +1,67 @@
+//! # Message verification
+//!
+//! Verify message signatures of different chains on the device through one interface.  The
+//! addresses are given as strings, so applications can pass user-provided input as is.  For all
+//! chains, an invalid address results in `Error::InvalidAddress` and an invalid signature in a
+//! failure response of the device.
+
+use bitcoin::network::constants::Network; //TODO(stevenroose) change after https://github.com/rust-bitcoin/rust-bitcoin/pull/181
+use bitcoin::Address;
+
+use client::{Trezor, TrezorResponse};
+use error::{Error, Result};
+use protos;
+
+/// A verifier of message signatures for a specific chain.
+pub trait MessageVerifier {
+	/// Verify the signature of the message by the address on the device.
+	fn verify_message<'a>(
+		&self,
+		trezor: &'a mut Trezor,
+		address: &str,
+		signature: &[u8],
+		message: Vec<u8>,
+	) -> Result<TrezorResponse<'a, (), protos::Success>>;
+}
+
+/// Verifier for Bitcoin message signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitcoinVerifier {
+	/// The network the addresses are expected to be on.
+	pub network: Network,
+}
+
+impl MessageVerifier for BitcoinVerifier {
+	fn verify_message<'a>(
+		&self,
+		trezor: &'a mut Trezor,
+		address: &str,
+		signature: &[u8],
+		message: Vec<u8>,
+	) -> Result<TrezorResponse<'a, (), protos::Success>> {
+		let mut addr: Address =
+			address.parse().map_err(|_| Error::InvalidAddress(address.to_owned()))?;
+		// Testnet and regtest can't always be distinguished by the address.
+		if (addr.network == Network::Bitcoin) != (self.network == Network::Bitcoin) {
+			return Err(Error::InvalidAddress(address.to_owned()));
+		}
+		addr.network = self.network;
+		trezor.verify_message(&addr, signature, message)
+	}
+}
+
+/// Verifier for Ethereum message signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthereumVerifier;
+
+impl MessageVerifier for EthereumVerifier {
+	fn verify_message<'a>(
+		&self,
+		trezor: &'a mut Trezor,
+		address: &str,
+		signature: &[u8],
+		message: Vec<u8>,
+	) -> Result<TrezorResponse<'a, (), protos::Success>> {
+		trezor.ethereum_verify_message(address, signature, message)
+	}
+}
@@ -0,0 +1,259 @@
+//! # BIP-39 wordlists
+//!
+//! Embedded BIP-39 wordlists and helpers to validate and complete mnemonic words.  These can be
+//! used to build host-assisted recovery interfaces.
+
+use std::fmt;
+
+use bitcoin_hashes::{sha256, Hash};
+use unicode_normalization::UnicodeNormalization;
+
+use error::{Error, Result};
+
+/// The number of words in a BIP-39 wordlist.
+const WORDLIST_SIZE: usize = 2048;
+
+/// The number of bits encoded by a single word.
+const BITS_PER_WORD: usize = 11;
+
+/// The languages for which a wordlist is available.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub enum Language {
+	English,
+	Spanish,
+	French,
+	Italian,
+}
+
+impl Default for Language {
+	fn default() -> Language {
+		Language::English
+	}
+}
+
+impl fmt::Display for Language {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Normalize a word to NFKD, which is the form used in the wordlists.
+fn normalize(word: &str) -> String {
+	word.trim().nfkd().collect()
+}
+
+impl Language {
+	/// The lowercase name of the language.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Language::English => "english",
+			Language::Spanish => "spanish",
+			Language::French => "french",
+			Language::Italian => "italian",
+		}
+	}
+
+	/// Whether the firmware accepts mnemonics in this language for recovery and uses it for new
+	/// seeds.  The firmware only supports the English wordlist.
+	pub fn supported_by_firmware(&self) -> bool {
+		*self == Language::English
+	}
+
+	fn raw_wordlist(&self) -> &'static str {
+		match *self {
+			Language::English => include_str!("wordlists/english.txt"),
+			Language::Spanish => include_str!("wordlists/spanish.txt"),
+			Language::French => include_str!("wordlists/french.txt"),
+			Language::Italian => include_str!("wordlists/italian.txt"),
+		}
+	}
+
+	/// All the words in the wordlist, in order.
+	pub fn words(&self) -> Vec<&'static str> {
+		let words: Vec<&'static str> = self.raw_wordlist().lines().collect();
+		debug_assert_eq!(WORDLIST_SIZE, words.len());
+		words
+	}
+
+	/// Get the index of the word in the wordlist.
+	pub fn word_index(&self, word: &str) -> Option<usize> {
+		let word = normalize(word);
+		self.raw_wordlist().lines().position(|w| w == word)
+	}
+
+	/// Check whether the word is in the wordlist.
+	pub fn is_valid_word(&self, word: &str) -> bool {
+		self.word_index(word).is_some()
+	}
+
+	/// All the words starting with the given prefix.
+	pub fn words_with_prefix(&self, prefix: &str) -> Vec<&'static str> {
+		let prefix = normalize(prefix);
+		self.raw_wordlist().lines().filter(|w| w.starts_with(&prefix)).collect()
+	}
+
+	/// Complete the given prefix to a full word, like the device does.  This returns a word if the
+	/// prefix is a word itself, or if it is the only word starting with the prefix.
+	pub fn complete_word(&self, prefix: &str) -> Option<&'static str> {
+		let words = self.words_with_prefix(prefix);
+		let prefix = normalize(prefix);
+		match words.iter().find(|w| **w == prefix) {
+			Some(word) => Some(word),
+			None if words.len() == 1 => Some(words[0]),
+			None => None,
+		}
+	}
+
+	/// Convert the entropy to a mnemonic using this wordlist.
+	pub fn entropy_to_mnemonic(&self, entropy: &[u8]) -> Result<String> {
+		if entropy.len() % 4 != 0 || entropy.len() < 16 || entropy.len() > 32 {
+			return Err(Error::InvalidEntropyStrength(entropy.len() * 8));
+		}
+
+		let checksum = sha256::Hash::hash(entropy).into_inner()[0];
+		let mut bits = Vec::with_capacity(entropy.len() * 8 + 8);
+		for byte in entropy.iter().chain(Some(&checksum)) {
+			for i in (0..8).rev() {
+				bits.push((byte >> i) & 1 == 1);
+			}
+		}
+		let nb_words = (entropy.len() * 8 + entropy.len() / 4) / BITS_PER_WORD;
+
+		let words = self.words();
+		let mnemonic: Vec<&str> = bits
+			.chunks(BITS_PER_WORD)
+			.take(nb_words)
+			.map(|c| words[c.iter().fold(0, |acc, b| (acc << 1) | (*b as usize))])
+			.collect();
+		Ok(mnemonic.join(" "))
+	}
+
+	/// Convert the mnemonic back to the entropy it encodes, verifying the checksum.
+	pub fn mnemonic_to_entropy(&self, mnemonic: &str) -> Result<Vec<u8>> {
+		let mnemonic = normalize(mnemonic);
+		let words: Vec<&str> = mnemonic.split_whitespace().collect();
+		if words.len() % 3 != 0 || words.len() < 12 || words.len() > 24 {
+			return Err(Error::InvalidMnemonic(format!(
+				"invalid number of words: {}",
+				words.len()
+			)));
+		}
+
+		let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+		for word in words {
+			let idx = self
+				.word_index(word)
+				.ok_or(Error::InvalidMnemonic(format!("unknown word: {}", word)))?;
+			for i in (0..BITS_PER_WORD).rev() {
+				bits.push((idx >> i) & 1 == 1);
+			}
+		}
+
+		let checksum_len = bits.len() / 33;
+		let entropy: Vec<u8> = bits[0..bits.len() - checksum_len]
+			.chunks(8)
+			.map(|c| c.iter().fold(0, |acc, b| (acc << 1) | (*b as u8)))
+			.collect();
+		let checksum = sha256::Hash::hash(&entropy).into_inner()[0] >> (8 - checksum_len);
+		let expected =
+			bits[bits.len() - checksum_len..].iter().fold(0, |acc, b| (acc << 1) | (*b as u8));
+		if checksum != expected {
+			return Err(Error::InvalidMnemonic("invalid checksum".to_owned()));
+		}
+		Ok(entropy)
+	}
+
+	/// Check whether the mnemonic consists of valid words and has a valid checksum.
+	pub fn is_valid_mnemonic(&self, mnemonic: &str) -> bool {
+		self.mnemonic_to_entropy(mnemonic).is_ok()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hex;
+
+	/// Test vectors from the BIP-39 reference implementation, for every entropy length.
+	const VECTORS: [(&str, &str); 8] = [
+		(
+			"00000000000000000000000000000000",
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+		),
+		(
+			"7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+			"legal winner thank year wave sausage worth useful legal winner thank yellow",
+		),
+		(
+			"80808080808080808080808080808080",
+			"letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+		),
+		("ffffffffffffffffffffffffffffffff", "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong"),
+		(
+			"9e885d952ad362caeb4efe34a8e91bd2",
+			"ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+		),
+		(
+			"7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+			"legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+		),
+		(
+			"0000000000000000000000000000000000000000000000000000000000000000",
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+		),
+		(
+			"f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+			"void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+		),
+	];
+
+	#[test]
+	fn entropy_to_mnemonic_vectors() {
+		for &(entropy, mnemonic) in VECTORS.iter() {
+			let entropy = hex::decode(entropy).unwrap();
+			assert_eq!(Language::English.entropy_to_mnemonic(&entropy).unwrap(), mnemonic);
+		}
+	}
+
+	#[test]
+	fn mnemonic_to_entropy_vectors() {
+		for &(entropy, mnemonic) in VECTORS.iter() {
+			let entropy = hex::decode(entropy).unwrap();
+			assert_eq!(Language::English.mnemonic_to_entropy(mnemonic).unwrap(), entropy);
+		}
+	}
+
+	#[test]
+	fn mnemonic_bad_checksum() {
+		// The last word of the first vector is "about".
+		let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+		match Language::English.mnemonic_to_entropy(mnemonic) {
+			Err(Error::InvalidMnemonic(ref e)) => assert_eq!(e, "invalid checksum"),
+			r => panic!("unexpected result: {:?}", r),
+		}
+		assert!(!Language::English.is_valid_mnemonic(mnemonic));
+	}
+
+	#[test]
+	fn complete_word_unique_prefix() {
+		assert_eq!(Language::English.complete_word("acti"), Some("action"));
+		assert_eq!(Language::English.complete_word("zoo"), Some("zoo"));
+		assert_eq!(Language::Spanish.complete_word("\u{e1}mba"), Some("a\u{301}mbar"));
+		assert_eq!(Language::English.complete_word("xyz"), None);
+	}
+
+	#[test]
+	fn complete_word_ambiguous_prefix() {
+		assert_eq!(Language::English.complete_word("ac"), None);
+		assert_eq!(Language::English.complete_word("ab"), None);
+		assert_eq!(Language::Spanish.complete_word("\u{e1}mb"), None);
+	}
+
+	#[test]
+	fn complete_word_exact_short_word() {
+		// "act" is a word itself, but also the prefix of "action", "actor", etc.
+		assert!(Language::English.words_with_prefix("act").len() > 1);
+		assert_eq!(Language::English.complete_word("act"), Some("act"));
+		assert_eq!(Language::Spanish.complete_word("asa"), Some("asa"));
+	}
+}
@@ -1,3 +1,12 @@
+//! # Raw protobuf messages
+//!
+//! The generated protobuf messages used to communicate with the device.  They are re-exported as
+//! `trezor::protos` for users that want to extend that library using `Trezor::call` and are
+//! considered unstable: they can change with any update of the protobuf definitions or the
+//! protobuf library.
+
+extern crate protobuf;
+
 pub mod messages;
 pub mod messages_bitcoin;
 pub mod messages_bootloader;